@@ -0,0 +1,110 @@
+extern crate shortestpath;
+
+use shortestpath::graph_builder::{load_geojson, LoadOptions, EdgeData};
+use shortestpath::search::{dijkstra_with_stats, astar_with_stats};
+use shortestpath::alt::{Landmarks, LandmarkPotential, Selection};
+use shortestpath::crp::{Overlay, crp_query};
+use shortestpath::ch::{ContractionHierarchy, ch_query};
+use shortestpath::tnr::TransitNodeRouting;
+use shortestpath::hub_labels::{HubLabels, partition_order};
+use shortestpath::partition::{MultilevelPartition, InertialFlow};
+use shortestpath::graph::{Graph, Node, Edge};
+use shortestpath::addressable_heap::{AddressableHeap, AddressableBinaryHeap};
+use shortestpath::dary_heap::AddressableQuaternaryHeap;
+use shortestpath::pairing_heap::AddressablePairingHeap;
+use shortestpath::radix_heap::AddressableRadixHeap;
+use shortestpath::bucket_queue::{BucketQueue, max_edge_weight};
+use shortestpath::handle_map::{HashHandleMap, PagedHandleMap};
+use shortestpath::benchmark::{random_queries, dijkstra_rank_queries, measure, Query};
+use shortestpath::random::XorShift;
+
+use std::env;
+
+fn bench_heap<G: Graph<EdgeData, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(name: &str, graph: &G, mut heap: H, queries: &[Query]) {
+    println!("{}", measure(name, queries, |source, target, stats| {
+        dijkstra_with_stats(graph, &mut heap, source, target, stats).unwrap().is_some()
+    }));
+}
+
+fn bench_alt<G: Graph<EdgeData, N=Node, E=Edge>>(name: &str, graph: &G, landmarks: &Landmarks, queries: &[Query]) {
+    let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+    println!("{}", measure(name, queries, |source, target, stats| {
+        let mut potential = LandmarkPotential::new(landmarks, target);
+        astar_with_stats(graph, &mut heap, &mut potential, source, target, stats).unwrap().is_some()
+    }));
+}
+
+const NUM_QUERIES : usize = 1000;
+const NUM_LANDMARKS : usize = 16;
+const CRP_CELL_SIZES : [usize; 3] = [256, 4096, 65536];
+const NUM_TRANSIT_NODES : usize = 1024;
+const HUB_ORDER_CELL_SIZES : [usize; 8] = [4, 16, 64, 256, 1024, 4096, 16384, 65536];
+const NUM_RANK_SOURCES : usize = 100;
+
+fn main() {
+    // cargo bench passes --bench to the binary
+    let path = env::args().skip(1).filter(|arg| !arg.starts_with("--")).next().unwrap_or(String::from("data/monaco.geojson"));
+    let network = load_geojson(&path, &LoadOptions::default()).unwrap();
+    let graph = network.graph;
+    println!("{}: {} nodes {} edges", path, graph.num_nodes(), graph.num_edges());
+
+    let mut random = XorShift::new(42);
+    let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+
+    let queries = random_queries(graph.num_nodes(), NUM_QUERIES, &mut random);
+    bench_heap("dijkstra binary", &graph, AddressableBinaryHeap::new(graph.num_nodes()), &queries);
+    bench_heap("dijkstra binary hash", &graph, AddressableBinaryHeap::with_handle_map(HashHandleMap::new()), &queries);
+    bench_heap("dijkstra binary paged", &graph, AddressableBinaryHeap::with_handle_map(PagedHandleMap::new(graph.num_nodes())), &queries);
+    bench_heap("dijkstra 4-ary", &graph, AddressableQuaternaryHeap::new(graph.num_nodes()), &queries);
+    bench_heap("dijkstra pairing", &graph, AddressablePairingHeap::new(graph.num_nodes()), &queries);
+    bench_heap("dijkstra radix", &graph, AddressableRadixHeap::new(graph.num_nodes()), &queries);
+    bench_heap("dijkstra dial", &graph, BucketQueue::new(graph.num_nodes(), max_edge_weight(&graph)), &queries);
+
+    let backward = graph.reversed();
+    for &(name, selection) in &[("alt random", Selection::Random), ("alt farthest", Selection::Farthest), ("alt avoid", Selection::Avoid)] {
+        let landmarks = Landmarks::select(&graph, &backward, NUM_LANDMARKS, selection, &mut random);
+        bench_alt(name, &graph, &landmarks, &queries);
+    }
+
+    let mut backward_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+    let partitions = vec![
+        ("crp coordinates", MultilevelPartition::from_coordinates(&network.coordinates, &CRP_CELL_SIZES)),
+        ("crp inertial flow", InertialFlow::default().partition(&graph, &network.coordinates, &CRP_CELL_SIZES))
+    ];
+    for (name, partition) in partitions {
+        let overlay = Overlay::new(&graph, partition);
+        let metric = overlay.customize(&graph, &mut heap).unwrap();
+        println!("{}", measure(name, &queries, |source, target, stats| {
+            crp_query(&graph, &backward, &overlay, &metric, &mut heap, &mut backward_heap, source, target, stats).unwrap().is_some()
+        }));
+    }
+
+    let ch = ContractionHierarchy::new(&graph).unwrap();
+    println!("ch: {} shortcuts", ch.num_shortcuts());
+    println!("{}", measure("ch", &queries, |source, target, stats| {
+        ch_query(&ch, &mut heap, &mut backward_heap, source, target, stats).unwrap().is_some()
+    }));
+    let tnr = TransitNodeRouting::new(ch, NUM_TRANSIT_NODES).unwrap();
+    let num_local = queries.iter().filter(|&&(source, target)| tnr.is_local(source, target)).count();
+    println!("tnr: {:.1} access nodes, {:.1} local search space nodes, {} local queries", tnr.average_access_nodes(), tnr.average_local_nodes(), num_local);
+    println!("{}", measure("tnr", &queries, |source, target, stats| {
+        tnr.query(&mut heap, &mut backward_heap, source, target, stats).unwrap().is_some()
+    }));
+
+    let partition = MultilevelPartition::from_coordinates(&network.coordinates, &HUB_ORDER_CELL_SIZES);
+    let labels = HubLabels::new(&graph, &backward, &partition_order(&graph, &partition)).unwrap();
+    println!("hub labels: {:.1} hubs per label", labels.average_label_size());
+    println!("{}", measure("hub labels", &queries, |source, target, _| {
+        labels.distance(source, target).is_some()
+    }));
+
+    let rank_queries = dijkstra_rank_queries(&graph, &mut heap, NUM_RANK_SOURCES, &mut random);
+    let max_rank = rank_queries.iter().map(|q| q.rank).max().unwrap_or(0);
+    for rank in 0..max_rank + 1 {
+        let queries : Vec<Query> = rank_queries.iter().filter(|q| q.rank == rank).map(|q| (q.source, q.target)).collect();
+        let name = format!("dijkstra rank 2^{}", rank);
+        println!("{}", measure(&name, &queries, |source, target, stats| {
+            dijkstra_with_stats(&graph, &mut heap, source, target, stats).unwrap().is_some()
+        }));
+    }
+}
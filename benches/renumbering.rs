@@ -0,0 +1,49 @@
+extern crate shortestpath;
+
+use shortestpath::graph_builder::{load_geojson, LoadOptions, RoadNetwork};
+use shortestpath::renumbering::{Renumbering, InputOrder, BfsOrder, DfsOrder, HilbertOrder, PartitionOrder};
+use shortestpath::search::dijkstra_with_stats;
+use shortestpath::graph::Graph;
+use shortestpath::addressable_heap::AddressableBinaryHeap;
+use shortestpath::benchmark::{measure, Query};
+use shortestpath::random::XorShift;
+
+use std::env;
+
+const NUM_QUERIES : usize = 1000;
+
+// queries are given as OSM node ids since they are stable under renumbering
+fn random_osm_queries(network: &RoadNetwork, count: usize) -> Vec<(i64, i64)> {
+    let osm_ids : Vec<i64> = network.id_map.keys().cloned().collect();
+    let mut random = XorShift::new(42);
+    (0..count).map(|_| {
+        let source = osm_ids[random.next_below(osm_ids.len())];
+        let target = osm_ids[random.next_below(osm_ids.len())];
+        (source, target)
+    }).collect()
+}
+
+fn bench<R: Renumbering>(name: &str, renumbering: R, path: &String, osm_queries: &[(i64, i64)]) {
+    let network = load_geojson(path, &LoadOptions::default()).unwrap();
+    let permutation = renumbering.permutation(&network.graph, &network.coordinates);
+    let network = network.renumber(&permutation);
+
+    let queries : Vec<Query> = osm_queries.iter().map(|&(source, target)| (network.id_map[&source], network.id_map[&target])).collect();
+    let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(network.graph.num_nodes());
+    println!("{}", measure(name, &queries, |source, target, stats| {
+        dijkstra_with_stats(&network.graph, &mut heap, source, target, stats).unwrap().is_some()
+    }));
+}
+
+fn main() {
+    // cargo bench passes --bench to the binary
+    let path = env::args().skip(1).filter(|arg| !arg.starts_with("--")).next().unwrap_or(String::from("data/monaco.geojson"));
+    let network = load_geojson(&path, &LoadOptions::default()).unwrap();
+    let queries = random_osm_queries(&network, NUM_QUERIES);
+
+    bench("input", InputOrder, &path, &queries);
+    bench("bfs", BfsOrder, &path, &queries);
+    bench("dfs", DfsOrder, &path, &queries);
+    bench("hilbert", HilbertOrder, &path, &queries);
+    bench("partition", PartitionOrder {cell_size: 64}, &path, &queries);
+}
@@ -1,4 +1,6 @@
-use std::fmt::Display;
+use handle_map::{HandleMap, DenseHandleMap, NOT_INSERTED, REMOVED};
+
+use std::fmt::Debug;
 
 // Implements a heap that stores a handle for each elements and allows modification
 // of the key associated witht the handle
@@ -7,11 +9,32 @@ pub trait AddressableHeap<Key> {
 
     fn len(&self) -> usize;
     fn min(&self) -> Option<(Self::Handle, Key)>;
+    // key of a handle that is currently in the heap
+    fn key(&self, Self::Handle) -> Key;
     fn push(&mut self, Self::Handle, Key);
     fn pop(&mut self) -> Option<(Self::Handle, Key)>;
     // if key is bigger than the current key this is noop
     fn decrease(&mut self, Self::Handle, Key);
+    // if key is smaller than the current key this is noop
+    fn increase(&mut self, Self::Handle, Key);
+    // removes a handle that is in the heap and returns its key,
+    // afterwards the handle counts as popped
+    fn remove(&mut self, Self::Handle) -> Key;
+    // sets the key to exactly the given value, pushes the handle if it is not in the heap
+    fn update(&mut self, handle: Self::Handle, key: Key) where Self::Handle: Copy, Key: Ord + Copy {
+        if !self.in_heap(handle) {
+            self.push(handle, key);
+        } else if key < self.key(handle) {
+            self.decrease(handle, key);
+        } else {
+            self.increase(handle, key);
+        }
+    }
     fn in_heap(&self, Self::Handle) -> bool;
+    // true if the handle was pushed at some point, even if it was popped since
+    fn was_inserted(&self, Self::Handle) -> bool;
+    // resets all handles so the heap can be reused for the next search
+    fn clear(&mut self);
 }
 
 #[derive(Clone, Copy)]
@@ -20,32 +43,38 @@ struct BinaryHeapElement<Key: Copy> {
     handle: u32
 }
 
-pub struct AddressableBinaryHeap<Key: Copy> {
+// The handle map decides how much memory is needed per search,
+// see handle_map for the alternatives to the dense default.
+pub struct AddressableBinaryHeap<Key: Copy, M: HandleMap = DenseHandleMap> {
     binary_tree: Vec<BinaryHeapElement<Key>>,
-    handle_to_index: Vec<u32>
+    handle_to_index: M
 }
 
-impl<Key: Copy + Ord + Display> AddressableBinaryHeap<Key> {
+impl<Key: Copy + Ord + Debug> AddressableBinaryHeap<Key> {
     pub fn new(num_handles: usize) -> AddressableBinaryHeap<Key> {
-        let mut handle_to_index : Vec<u32> = Vec::new();
-        handle_to_index.resize(num_handles, u32::max_value());
+        AddressableBinaryHeap::with_handle_map(DenseHandleMap::new(num_handles))
+    }
+}
+
+impl<Key: Copy + Ord + Debug, M: HandleMap> AddressableBinaryHeap<Key, M> {
+    pub fn with_handle_map(handle_to_index: M) -> AddressableBinaryHeap<Key, M> {
         AddressableBinaryHeap {binary_tree: vec![], handle_to_index: handle_to_index}
     }
 
     fn update_handle(&mut self, index: usize) {
         let handle = self.binary_tree[index as usize].handle;
-        self.handle_to_index[handle as usize] = index as u32;
+        self.handle_to_index.set(handle, index as u32);
     }
 
     fn dump_heap(&self) {
         println!("### binary_tree:");
         for ref elem in &self.binary_tree {
-            println!("{}, {}", elem.key, elem.handle);
+            println!("{:?}, {}", elem.key, elem.handle);
         }
 
         println!("### handle_to_index:");
-        for (h, v) in self.handle_to_index.iter().enumerate() {
-            println!("{} -> {}", h, v);
+        for ref elem in &self.binary_tree {
+            println!("{} -> {}", elem.handle, self.handle_to_index.get(elem.handle));
         }
     }
 
@@ -84,7 +113,8 @@ impl<Key: Copy + Ord + Display> AddressableBinaryHeap<Key> {
         }
     }
 
-    // swaps down the hole created by removing the top at index
+    // swaps the element at index down until the sub-heap at index is valid again,
+    // works for any position e.g. after removing the top or increasing a key
     fn heap_down(&mut self, index: usize) {
         let mut parent_index = index;
         let mut left_index = self.left_child(parent_index);
@@ -119,7 +149,7 @@ impl<Key: Copy + Ord + Display> AddressableBinaryHeap<Key> {
     }
 }
 
-impl<Key: Ord + Copy + Display> AddressableHeap<Key> for AddressableBinaryHeap<Key> {
+impl<Key: Ord + Copy + Debug, M: HandleMap> AddressableHeap<Key> for AddressableBinaryHeap<Key, M> {
     type Handle = u32;
 
     fn len(&self) -> usize {
@@ -130,9 +160,14 @@ impl<Key: Ord + Copy + Display> AddressableHeap<Key> for AddressableBinaryHeap<K
         self.binary_tree.first().map(|top| (top.handle, top.key))
     }
 
+    fn key(&self, handle: Self::Handle) -> Key {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        self.binary_tree[self.handle_to_index.get(handle) as usize].key
+    }
+
     fn push(&mut self, h: Self::Handle, k: Key) {
         let tree_index = self.binary_tree.len();
-        self.handle_to_index[h as usize] = tree_index as u32;
+        self.handle_to_index.set(h, tree_index as u32);
         self.binary_tree.push(BinaryHeapElement {handle: h, key: k});
         self.heap_up(tree_index);
     }
@@ -142,23 +177,28 @@ impl<Key: Ord + Copy + Display> AddressableHeap<Key> for AddressableBinaryHeap<K
             return None;
         }
 
-        if self.binary_tree.len() > 1 {
+        let element = if self.binary_tree.len() > 1 {
             let element = self.binary_tree.swap_remove(0);
             self.update_handle(0);
             self.heap_down(0);
-            Some((element.handle, element.key))
+            element
         } else {
             let element = self.binary_tree[0];
             self.binary_tree.clear();
-            Some((element.handle, element.key))
-        }
+            element
+        };
+        self.handle_to_index.set(element.handle, REMOVED);
+        Some((element.handle, element.key))
     }
 
     fn decrease(&mut self, handle: Self::Handle, k: Key) {
-        let index = self.handle_to_index[handle as usize];
-        if index == u32::max_value() {
+        let index = self.handle_to_index.get(handle);
+        if index == NOT_INSERTED {
             panic!("Handle {} is was not inserted yet", handle);
         }
+        if index == REMOVED {
+            panic!("Handle {} was already removed", handle);
+        }
 
         if self.binary_tree[index as usize].key <= k {
             return;
@@ -169,14 +209,52 @@ impl<Key: Ord + Copy + Display> AddressableHeap<Key> for AddressableBinaryHeap<K
         self.heap_up(index as usize);
     }
 
+    fn increase(&mut self, handle: Self::Handle, k: Key) {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle) as usize;
+        if self.binary_tree[index].key >= k {
+            return;
+        }
+
+        self.binary_tree[index].key = k;
+        self.heap_down(index);
+    }
+
+    fn remove(&mut self, handle: Self::Handle) -> Key {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle) as usize;
+        let element = self.binary_tree.swap_remove(index);
+        // the last element was moved into the hole and can violate the heap in both directions
+        if index < self.binary_tree.len() {
+            let moved = self.binary_tree[index].handle;
+            self.update_handle(index);
+            self.heap_up(index);
+            if self.handle_to_index.get(moved) as usize == index {
+                self.heap_down(index);
+            }
+        }
+        self.handle_to_index.set(handle, REMOVED);
+        element.key
+    }
+
     fn in_heap(&self, handle: Self::Handle) -> bool {
-        self.handle_to_index[handle as usize] != u32::max_value()
+        self.handle_to_index.get(handle) < REMOVED
+    }
+
+    fn was_inserted(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) != NOT_INSERTED
+    }
+
+    fn clear(&mut self) {
+        self.handle_to_index.clear();
+        self.binary_tree.clear();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use handle_map::HashHandleMap;
 
     #[test]
     fn new_empty() {
@@ -251,6 +329,7 @@ mod tests {
         h.push(0, 2);
         h.push(9, 1);
         assert_eq!(h.min(), Some((9, 1)));
+        assert_eq!(h.key(5), 4);
         // should be a noop
         h.decrease(9, 5);
         assert_eq!(h.min(), Some((9, 1)));
@@ -268,5 +347,98 @@ mod tests {
         assert_eq!(h.min(), Some((0, 2)));
     }
 
+    #[test]
+    fn pop_removes_handle() {
+        let mut h : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(10);
+        h.push(5, 1);
+        h.push(6, 2);
+        assert!(!h.was_inserted(7));
+        assert_eq!(h.pop(), Some((5, 1)));
+        assert!(!h.in_heap(5));
+        assert!(h.was_inserted(5));
+        assert!(h.in_heap(6));
+        assert_eq!(h.pop(), Some((6, 2)));
+        assert!(!h.in_heap(6));
+        assert!(h.was_inserted(6));
+    }
+
+    #[test]
+    fn increase_key() {
+        let mut h : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(10);
+        for handle in 0..7 {
+            h.push(handle, handle);
+        }
+        // should be a noop
+        h.increase(3, 1);
+        assert_eq!(h.key(3), 3);
+        h.increase(0, 10);
+        h.increase(1, 4);
+        assert_eq!(h.pop(), Some((2, 2)));
+        assert_eq!(h.pop(), Some((3, 3)));
+        assert_eq!(h.pop().map(|(_, k)| k), Some(4));
+        assert_eq!(h.pop().map(|(_, k)| k), Some(4));
+        assert_eq!(h.pop(), Some((5, 5)));
+        assert_eq!(h.pop(), Some((6, 6)));
+        assert_eq!(h.pop(), Some((0, 10)));
+    }
+
+    #[test]
+    fn remove_inner() {
+        let mut h : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(10);
+        // the last element is moved into a hole in another subtree and has to move up
+        for (handle, &key) in [0, 10, 1, 11, 12, 2, 3].iter().enumerate() {
+            h.push(handle as u32, key);
+        }
+        assert_eq!(h.remove(3), 11);
+        assert!(!h.in_heap(3));
+        assert!(h.was_inserted(3));
+        assert_eq!(h.len(), 6);
+        let keys : Vec<u32> = (0..6).map(|_| h.pop().unwrap().1).collect();
+        assert_eq!(keys, vec![0, 1, 2, 3, 10, 12]);
+
+        h.push(3, 5);
+        assert_eq!(h.remove(3), 5);
+        assert_eq!(h.len(), 0);
+    }
+
+    #[test]
+    fn update_key() {
+        let mut h : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(10);
+        h.update(1, 5);
+        h.update(2, 3);
+        assert_eq!(h.min(), Some((2, 3)));
+        h.update(2, 7);
+        assert_eq!(h.min(), Some((1, 5)));
+        h.update(1, 9);
+        assert_eq!(h.min(), Some((2, 7)));
+        h.update(1, 0);
+        assert_eq!(h.min(), Some((1, 0)));
+    }
+
+    #[test]
+    fn sparse_handles() {
+        let mut h = AddressableBinaryHeap::with_handle_map(HashHandleMap::new());
+        h.push(4000000000, 3);
+        h.push(7, 1);
+        assert!(h.in_heap(4000000000));
+        assert_eq!(h.pop(), Some((7, 1)));
+        assert_eq!(h.pop(), Some((4000000000, 3)));
+        assert!(h.was_inserted(4000000000));
+    }
+
+    #[test]
+    fn clear() {
+        let mut h : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(10);
+        h.push(5, 1);
+        h.push(6, 2);
+        h.pop();
+        h.clear();
+        assert_eq!(h.len(), 0);
+        assert!(!h.was_inserted(5));
+        assert!(!h.was_inserted(6));
+        h.push(6, 0);
+        assert_eq!(h.min(), Some((6, 0)));
+    }
+
 
 }
@@ -0,0 +1,357 @@
+use addressable_heap::AddressableBinaryHeap;
+use graph::{Graph, Node, Edge};
+use search::{WeightedData, SearchVisitor, Potential, dijkstra_one_to_all};
+use random::XorShift;
+use weight::Weight;
+
+use std::io;
+use std::io::{Read, Write};
+
+// How landmarks are picked, see Goldberg and Harrelson,
+// "Computing the Shortest Path: A* Search Meets Graph Theory" and
+// Goldberg and Werneck, "Computing Point-to-Point Shortest Paths from External Memory".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    // uniformly at random
+    Random,
+    // every landmark is the node farthest away from the ones picked so far
+    Farthest,
+    // landmarks are placed in regions of a shortest path tree
+    // where the current lower bounds are bad
+    Avoid
+}
+
+// Distances from and to a set of landmark nodes. Together with the triangle inequality
+// they give lower bounds d(v, t) >= d(l, t) - d(l, v) and d(v, t) >= d(v, l) - d(t, l)
+// that are used as A* potential.
+#[derive(Debug, PartialEq)]
+pub struct Landmarks {
+    landmarks: Vec<Node>,
+    num_nodes: usize,
+    // from[i][v] = d(landmarks[i], v), unreachable nodes have infinite distance
+    from: Vec<Vec<u32>>,
+    // to[i][v] = d(v, landmarks[i])
+    to: Vec<Vec<u32>>
+}
+
+// shortest path tree of a one-to-all search
+struct ShortestPathTree {
+    order: Vec<Node>,
+    distances: Vec<u32>,
+    parents: Vec<Node>
+}
+
+impl ShortestPathTree {
+    fn new(num_nodes: usize) -> ShortestPathTree {
+        ShortestPathTree {order: Vec::new(), distances: vec![u32::infinity(); num_nodes], parents: vec![u32::infinity(); num_nodes]}
+    }
+}
+
+impl SearchVisitor<u32> for ShortestPathTree {
+    fn settle(&mut self, node: Node, weight: u32) {
+        self.order.push(node);
+        self.distances[node as usize] = weight;
+    }
+
+    fn push(&mut self, source: Node, _: Edge, target: Node, _: u32) {
+        self.parents[target as usize] = source;
+    }
+
+    fn decrease(&mut self, source: Node, _: Edge, target: Node, _: u32) {
+        self.parents[target as usize] = source;
+    }
+}
+
+fn shortest_path_tree<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, heap: &mut AddressableBinaryHeap<u32>, source: Node) -> ShortestPathTree {
+    let mut tree = ShortestPathTree::new(graph.num_nodes());
+    dijkstra_one_to_all(graph, heap, source, &mut tree).unwrap();
+    tree
+}
+
+impl Landmarks {
+    // Picks count landmarks, backward has to be the reversed forward graph.
+    pub fn select<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(forward: &G, backward: &G, count: usize, selection: Selection, random: &mut XorShift) -> Landmarks {
+        let num_nodes = forward.num_nodes();
+        let mut landmarks = Landmarks {landmarks: Vec::new(), num_nodes: num_nodes, from: Vec::new(), to: Vec::new()};
+        let mut heap = AddressableBinaryHeap::new(num_nodes);
+
+        while landmarks.len() < count.min(num_nodes) {
+            let landmark = match selection {
+                Selection::Random => {
+                    let mut node = random.next_below(num_nodes) as Node;
+                    while landmarks.landmarks.contains(&node) {
+                        node = random.next_below(num_nodes) as Node;
+                    }
+                    node
+                },
+                Selection::Farthest => landmarks.farthest(forward, &mut heap, random),
+                Selection::Avoid => {
+                    if landmarks.len() == 0 {
+                        landmarks.farthest(forward, &mut heap, random)
+                    } else {
+                        landmarks.avoid(forward, &mut heap, random)
+                    }
+                }
+            };
+            landmarks.add(forward, backward, &mut heap, landmark);
+        }
+
+        landmarks
+    }
+
+    // Uses the given nodes as landmarks, backward has to be the reversed forward graph.
+    pub fn with_nodes<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(forward: &G, backward: &G, nodes: &[Node]) -> Landmarks {
+        let mut landmarks = Landmarks {landmarks: Vec::new(), num_nodes: forward.num_nodes(), from: Vec::new(), to: Vec::new()};
+        let mut heap = AddressableBinaryHeap::new(forward.num_nodes());
+        for &node in nodes {
+            landmarks.add(forward, backward, &mut heap, node);
+        }
+        landmarks
+    }
+
+    fn add<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(&mut self, forward: &G, backward: &G, heap: &mut AddressableBinaryHeap<u32>, landmark: Node) {
+        self.landmarks.push(landmark);
+        self.from.push(shortest_path_tree(forward, heap, landmark).distances);
+        self.to.push(shortest_path_tree(backward, heap, landmark).distances);
+    }
+
+    // The first landmark is the node farthest from a random node, every following one
+    // maximizes the distance from or to the closest landmark. Nodes not connected to
+    // any landmark are preferred, so every component gets a landmark.
+    fn farthest<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(&self, forward: &G, heap: &mut AddressableBinaryHeap<u32>, random: &mut XorShift) -> Node {
+        let closest : Vec<u32> = if self.landmarks.is_empty() {
+            let start = random.next_below(self.num_nodes) as Node;
+            let distances = shortest_path_tree(forward, heap, start).distances;
+            distances.into_iter().map(|distance| if distance == u32::infinity() { 0 } else { distance }).collect()
+        } else {
+            (0..self.num_nodes).map(|node| {
+                self.from.iter().zip(self.to.iter()).map(|(from, to)| from[node].min(to[node])).min().unwrap()
+            }).collect()
+        };
+
+        let mut best : Option<usize> = None;
+        for node in 0..self.num_nodes {
+            if self.landmarks.contains(&(node as Node)) {
+                continue;
+            }
+            if best.map_or(true, |best| closest[node] > closest[best]) {
+                best = Some(node);
+            }
+        }
+        best.unwrap() as Node
+    }
+
+    // Grows a shortest path tree from a random root. Every node is weighted with how much
+    // the current lower bound from the root underestimates its distance. Starting at the
+    // subtree with the biggest total weight that contains no landmark, the heaviest child
+    // is followed down to a leaf which becomes the next landmark.
+    fn avoid<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(&self, forward: &G, heap: &mut AddressableBinaryHeap<u32>, random: &mut XorShift) -> Node {
+        let root = random.next_below(self.num_nodes) as Node;
+        let tree = shortest_path_tree(forward, heap, root);
+
+        let mut sizes : Vec<u64> = vec![0; self.num_nodes];
+        let mut has_landmark = vec![false; self.num_nodes];
+        let mut heaviest_child : Vec<Node> = vec![u32::infinity(); self.num_nodes];
+        // children are settled after their parent
+        for &node in tree.order.iter().rev() {
+            let idx = node as usize;
+            if self.landmarks.contains(&node) {
+                has_landmark[idx] = true;
+            }
+            if has_landmark[idx] {
+                sizes[idx] = 0;
+            } else {
+                sizes[idx] += (tree.distances[idx] - self.lower_bound(root, node)) as u64;
+            }
+
+            if node != root {
+                let parent = tree.parents[idx] as usize;
+                has_landmark[parent] = has_landmark[parent] || has_landmark[idx];
+                sizes[parent] += sizes[idx];
+                if heaviest_child[parent] == u32::infinity() || sizes[idx] > sizes[heaviest_child[parent] as usize] {
+                    heaviest_child[parent] = node;
+                }
+            }
+        }
+
+        let mut best = root;
+        for &node in &tree.order {
+            if sizes[node as usize] > sizes[best as usize] {
+                best = node;
+            }
+        }
+        if sizes[best as usize] == 0 {
+            // every subtree already contains a landmark
+            return self.farthest(forward, heap, random);
+        }
+
+        while heaviest_child[best as usize] != u32::infinity() {
+            best = heaviest_child[best as usize];
+        }
+        best
+    }
+
+    pub fn len(&self) -> usize {
+        self.landmarks.len()
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.landmarks
+    }
+
+    // Lower bound of the distance from source to target, the best bound over all landmarks.
+    pub fn lower_bound(&self, source: Node, target: Node) -> u32 {
+        let (source, target) = (source as usize, target as usize);
+        let mut bound = 0;
+        for (from, to) in self.from.iter().zip(self.to.iter()) {
+            if from[source] != u32::infinity() && from[target] != u32::infinity() && from[target] > from[source] {
+                bound = bound.max(from[target] - from[source]);
+            }
+            if to[source] != u32::infinity() && to[target] != u32::infinity() && to[source] > to[target] {
+                bound = bound.max(to[source] - to[target]);
+            }
+        }
+        bound
+    }
+
+    // Format: number of nodes and landmarks followed by the landmark ids and the
+    // from and to distances of every landmark, all as little endian u32.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(write_u32(writer, self.num_nodes as u32));
+        try!(write_u32(writer, self.landmarks.len() as u32));
+        for &landmark in &self.landmarks {
+            try!(write_u32(writer, landmark));
+        }
+        for (from, to) in self.from.iter().zip(self.to.iter()) {
+            for &distance in from.iter().chain(to.iter()) {
+                try!(write_u32(writer, distance));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Landmarks> {
+        let num_nodes = try!(read_u32(reader)) as usize;
+        let num_landmarks = try!(read_u32(reader)) as usize;
+        let mut landmarks = Landmarks {landmarks: Vec::with_capacity(num_landmarks), num_nodes: num_nodes, from: Vec::new(), to: Vec::new()};
+        for _ in 0..num_landmarks {
+            let landmark = try!(read_u32(reader));
+            if landmark as usize >= num_nodes {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "landmark is not a valid node"));
+            }
+            landmarks.landmarks.push(landmark);
+        }
+        for _ in 0..num_landmarks {
+            landmarks.from.push(try!(read_distances(reader, num_nodes)));
+            landmarks.to.push(try!(read_distances(reader, num_nodes)));
+        }
+        Ok(landmarks)
+    }
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    try!(reader.read_exact(&mut bytes));
+    Ok(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24)
+}
+
+fn read_distances<R: Read>(reader: &mut R, num_nodes: usize) -> io::Result<Vec<u32>> {
+    let mut distances = Vec::with_capacity(num_nodes);
+    for _ in 0..num_nodes {
+        distances.push(try!(read_u32(reader)));
+    }
+    Ok(distances)
+}
+
+// A* potential for queries towards a fixed target
+pub struct LandmarkPotential<'a> {
+    landmarks: &'a Landmarks,
+    target: Node
+}
+
+impl<'a> LandmarkPotential<'a> {
+    pub fn new(landmarks: &'a Landmarks, target: Node) -> LandmarkPotential<'a> {
+        LandmarkPotential {landmarks: landmarks, target: target}
+    }
+}
+
+impl<'a> Potential for LandmarkPotential<'a> {
+    fn potential(&mut self, node: Node) -> u32 {
+        self.landmarks.lower_bound(node, self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use search::*;
+    use test_util::{TestData, grid};
+
+    fn check_queries(selection: Selection) {
+        let forward = grid(8, 6);
+        let backward = forward.reversed();
+        let mut random = XorShift::new(7);
+        let landmarks = Landmarks::select(&forward, &backward, 4, selection, &mut random);
+        assert_eq!(landmarks.len(), 4);
+
+        let mut dijkstra_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(forward.num_nodes());
+        let mut astar_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(forward.num_nodes());
+        let mut dijkstra_stats = SearchStats::default();
+        let mut astar_stats = SearchStats::default();
+        for source in forward.nodes() {
+            for target in forward.nodes() {
+                let expected = dijkstra_with_stats(&forward, &mut dijkstra_heap, source, target, &mut dijkstra_stats).unwrap();
+                let mut potential = LandmarkPotential::new(&landmarks, target);
+                assert!(landmarks.lower_bound(source, target) <= expected.unwrap());
+                assert_eq!(astar_with_stats(&forward, &mut astar_heap, &mut potential, source, target, &mut astar_stats).unwrap(), expected);
+            }
+        }
+        assert!(astar_stats.settled < dijkstra_stats.settled);
+    }
+
+    #[test]
+    fn random_landmarks() {
+        check_queries(Selection::Random);
+    }
+
+    #[test]
+    fn farthest_landmarks() {
+        check_queries(Selection::Farthest);
+    }
+
+    #[test]
+    fn avoid_landmarks() {
+        check_queries(Selection::Avoid);
+    }
+
+    // 0 <-> 1 -> 2    3 <-> 4
+    #[test]
+    fn farthest_covers_components() {
+        let forward : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 0, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (3, 4, TestData {weight: 1}), (4, 3, TestData {weight: 1})]);
+        let backward = forward.reversed();
+        let landmarks = Landmarks::select(&forward, &backward, 2, Selection::Farthest, &mut XorShift::new(3));
+        let mut nodes = landmarks.nodes().to_vec();
+        nodes.sort();
+        assert!(nodes[0] <= 2 && nodes[1] >= 3);
+        assert_eq!(landmarks.lower_bound(0, 4), 0);
+    }
+
+    #[test]
+    fn write_and_read() {
+        let forward = grid(4, 3);
+        let backward = forward.reversed();
+        let landmarks = Landmarks::with_nodes(&forward, &backward, &[0, 11]);
+        assert_eq!(landmarks.lower_bound(0, 11), landmarks.from[0][11]);
+
+        let mut buffer = Vec::new();
+        landmarks.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 4 * (2 + 2 + 2 * 2 * 12));
+        assert_eq!(Landmarks::read(&mut &buffer[..]).unwrap(), landmarks);
+        assert!(Landmarks::read(&mut &buffer[..buffer.len() - 1]).is_err());
+    }
+}
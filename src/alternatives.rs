@@ -0,0 +1,344 @@
+use addressable_heap::AddressableBinaryHeap;
+use graph::{Graph, Node, Edge};
+use path::Path;
+use search::{WeightedData, SearchVisitor, SearchError, dijkstra, dijkstra_with_visitor, dijkstra_bounded};
+use traffic::LiveWeights;
+use weight::Weight;
+
+use std::collections::{HashMap, HashSet};
+
+pub struct AlternativeOptions {
+    // number of alternatives besides the shortest path
+    pub max_alternatives: usize,
+    // an alternative may be at most this factor longer than the shortest path
+    pub max_stretch: f64,
+    // weight an alternative shares with every other returned path, relative to the shortest path
+    pub max_sharing: f64,
+    // every subpath up to this fraction of the shortest path has to be a shortest path
+    pub local_optimality: f64,
+    // factor the weight of edges on found paths is multiplied with by the penalty method
+    pub penalty: f64,
+    // number of penalized searches when the via nodes do not give enough alternatives
+    pub penalty_iterations: usize
+}
+
+impl Default for AlternativeOptions {
+    fn default() -> AlternativeOptions {
+        AlternativeOptions {max_alternatives: 2, max_stretch: 1.25, max_sharing: 0.8, local_optimality: 0.25, penalty: 1.5, penalty_iterations: 8}
+    }
+}
+
+// settled nodes with their weight and the last edge of their path
+type Tree = HashMap<Node, (u32, Option<(Node, Edge)>)>;
+
+// Records the settled nodes with their weight and the last edge of their path
+struct TreeVisitor {
+    parents: HashMap<Node, (Node, Edge)>,
+    tree: Tree
+}
+
+impl SearchVisitor<u32> for TreeVisitor {
+    fn settle(&mut self, node: Node, weight: u32) {
+        let parent = self.parents.get(&node).cloned();
+        self.tree.insert(node, (weight, parent));
+    }
+
+    fn push(&mut self, source: Node, edge: Edge, target: Node, _: u32) {
+        self.parents.insert(target, (source, edge));
+    }
+
+    fn decrease(&mut self, source: Node, edge: Edge, target: Node, _: u32) {
+        self.parents.insert(target, (source, edge));
+    }
+}
+
+// Dijkstra that stops at target or, without a target, settles the nodes up to max_weight
+fn search<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, heap: &mut AddressableBinaryHeap<u32>, source: Node, target: Option<Node>, max_weight: u32) -> Result<Tree, SearchError> {
+    let mut visitor = TreeVisitor {parents: HashMap::new(), tree: Tree::new()};
+    match target {
+        Some(target) => {
+            try!(dijkstra_with_visitor(graph, heap, source, target, &mut visitor));
+        },
+        None => try!(dijkstra_bounded(graph, heap, source, max_weight, &mut visitor))
+    }
+    Ok(visitor.tree)
+}
+
+// nodes and edges from the root of the tree to node
+fn tree_path(tree: &Tree, node: Node) -> (Vec<Node>, Vec<Edge>) {
+    let mut nodes = vec![node];
+    let mut edges = Vec::new();
+    let mut current = node;
+    while let Some(&(_, Some((parent, edge)))) = tree.get(&current) {
+        nodes.push(parent);
+        edges.push(edge);
+        current = parent;
+    }
+    nodes.reverse();
+    edges.reverse();
+    (nodes, edges)
+}
+
+// lightest edge from source to target in graph
+fn find_edge<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, source: Node, target: Node) -> Edge {
+    graph.edges(source).filter(|&edge| graph.target(edge) == target).min_by_key(|&edge| graph.data(edge).weight()).unwrap()
+}
+
+fn path_weight<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, edges: &[Edge]) -> u32 {
+    edges.iter().fold(0, |weight: u32, &edge| weight.saturating_add(graph.data(edge).weight()))
+}
+
+fn is_simple(path: &Path<u32>) -> bool {
+    let mut visited = HashSet::new();
+    path.nodes.iter().all(|&node| visited.insert(node))
+}
+
+// Collects the accepted paths and checks new candidates against them
+struct Selection<'a> {
+    options: &'a AlternativeOptions,
+    shortest: u32,
+    paths: Vec<Path<u32>>,
+    edges: Vec<HashSet<Edge>>
+}
+
+impl<'a> Selection<'a> {
+    fn new(options: &'a AlternativeOptions, shortest: Path<u32>) -> Selection<'a> {
+        let edges = shortest.edges.iter().cloned().collect();
+        Selection {options: options, shortest: shortest.weight, paths: vec![shortest], edges: vec![edges]}
+    }
+
+    fn is_full(&self) -> bool {
+        self.paths.len() > self.options.max_alternatives
+    }
+
+    fn max_weight(&self) -> u32 {
+        (self.shortest as f64 * self.options.max_stretch).min(u32::infinity() as f64 - 1.0) as u32
+    }
+
+    // most weight the candidate shares with one of the accepted paths
+    fn sharing<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(&self, graph: &G, candidate: &Path<u32>) -> u32 {
+        self.edges.iter().map(|edges| {
+            let shared : Vec<Edge> = candidate.edges.iter().cloned().filter(|edge| edges.contains(edge)).collect();
+            path_weight(graph, &shared)
+        }).max().unwrap_or(0)
+    }
+
+    fn is_admissible<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(&self, graph: &G, candidate: &Path<u32>) -> bool {
+        candidate.weight <= self.max_weight()
+            && !self.paths.iter().any(|path| path.edges == candidate.edges)
+            && is_simple(candidate)
+            && (self.sharing(graph, candidate) as f64) <= self.options.max_sharing * self.shortest as f64
+    }
+
+    fn add(&mut self, path: Path<u32>) {
+        self.edges.push(path.edges.iter().cloned().collect());
+        self.paths.push(path);
+    }
+}
+
+// The subpath around the via node that covers local_optimality of the shortest path
+// in both directions has to be a shortest path itself, otherwise the alternative
+// contains an obvious detour.
+fn is_locally_optimal<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, heap: &mut AddressableBinaryHeap<u32>, path: &Path<u32>, via_index: usize, length: u32) -> Result<bool, SearchError> {
+    let mut prefix = vec![0u32];
+    for &edge in &path.edges {
+        let last = *prefix.last().unwrap();
+        prefix.push(last.saturating_add(graph.data(edge).weight()));
+    }
+    let via_weight = prefix[via_index];
+    let first = (0..via_index + 1).rev().find(|&index| via_weight - prefix[index] >= length).unwrap_or(0);
+    let last = (via_index..prefix.len()).find(|&index| prefix[index] - via_weight >= length).unwrap_or(prefix.len() - 1);
+    let distance = try!(dijkstra(graph, heap, path.nodes[first], path.nodes[last]));
+    Ok(distance == Some(prefix[last] - prefix[first]))
+}
+
+// Via node alternatives, see Abraham et al., "Alternative Routes in Road Networks".
+// A forward search from the source and a backward search from the target settle every
+// node up to the maximum stretch. Each node settled by both gives the path over it,
+// the candidates are tried by their weight plus the weight shared with the shortest path.
+fn via_node_alternatives<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(forward: &G, backward: &G, heap: &mut AddressableBinaryHeap<u32>, selection: &mut Selection, source: Node, target: Node) -> Result<(), SearchError> {
+    let max_weight = selection.max_weight();
+    let forward_tree = try!(search(forward, heap, source, None, max_weight));
+    let backward_tree = try!(search(backward, heap, target, None, max_weight));
+
+    let mut candidates = Vec::new();
+    let mut seen = HashSet::new();
+    for (&via, &(forward_weight, _)) in &forward_tree {
+        let backward_weight = match backward_tree.get(&via) {
+            Some(&(backward_weight, _)) => backward_weight,
+            None => continue
+        };
+        if forward_weight.saturating_add(backward_weight) > max_weight {
+            continue;
+        }
+        let (mut nodes, mut edges) = tree_path(&forward_tree, via);
+        let via_index = edges.len();
+        // the backward tree contains reversed edges, their forward counterparts have the same weight
+        let (backward_nodes, _) = tree_path(&backward_tree, via);
+        for pair in backward_nodes.windows(2).rev() {
+            edges.push(find_edge(forward, pair[1], pair[0]));
+            nodes.push(pair[0]);
+        }
+        if !seen.insert(edges.clone()) {
+            continue;
+        }
+        let path = Path {weight: path_weight(forward, &edges), nodes: nodes, edges: edges};
+        let shared = selection.sharing(forward, &path);
+        candidates.push((path.weight.saturating_add(shared), via_index, path));
+    }
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then(a.2.edges.cmp(&b.2.edges)));
+
+    let length = (selection.shortest as f64 * selection.options.local_optimality) as u32;
+    for (_, via_index, path) in candidates {
+        if selection.is_full() {
+            break;
+        }
+        if selection.is_admissible(forward, &path) && try!(is_locally_optimal(forward, heap, &path, via_index, length)) {
+            selection.add(path);
+        }
+    }
+    Ok(())
+}
+
+// Penalty method: the edges of all found paths get more expensive,
+// so the next search avoids them where a detour is cheap.
+fn penalty_alternatives<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(forward: &G, heap: &mut AddressableBinaryHeap<u32>, selection: &mut Selection, source: Node, target: Node) -> Result<(), SearchError> {
+    let mut weights = LiveWeights::new(forward);
+    let mut penalties : HashMap<Edge, f64> = HashMap::new();
+    let penalty = selection.options.penalty;
+    let found : Vec<Edge> = selection.paths.iter().flat_map(|path| path.edges.iter().cloned()).collect();
+    penalize(forward, &mut weights, &mut penalties, &found, penalty);
+
+    for _ in 0..selection.options.penalty_iterations {
+        if selection.is_full() {
+            break;
+        }
+        let tree = try!(search(&weights.graph(forward), heap, source, Some(target), u32::infinity()));
+        if !tree.contains_key(&target) {
+            break;
+        }
+        let (nodes, edges) = tree_path(&tree, target);
+        penalize(forward, &mut weights, &mut penalties, &edges, penalty);
+        let path = Path {weight: path_weight(forward, &edges), nodes: nodes, edges: edges};
+        if selection.is_admissible(forward, &path) {
+            selection.add(path);
+        }
+    }
+    Ok(())
+}
+
+// multiplies the penalty factor of every edge by penalty, closed edges stay closed
+fn penalize<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(forward: &G, weights: &mut LiveWeights, penalties: &mut HashMap<Edge, f64>, edges: &[Edge], penalty: f64) {
+    for &edge in edges {
+        let factor = penalties.entry(edge).or_insert(1.0);
+        *factor *= penalty;
+        let weight = forward.data(edge).weight();
+        if weight != u32::infinity() {
+            weights.set(edge, (weight as f64 * *factor).min(u32::infinity() as f64 - 1.0) as u32);
+        }
+    }
+}
+
+// The shortest path followed by up to max_alternatives alternatives, ranked by quality.
+// Via node alternatives come first, the penalty method fills up the remaining slots.
+// backward has to be the reversed forward graph.
+pub fn alternatives<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(forward: &G, backward: &G, heap: &mut AddressableBinaryHeap<u32>, source: Node, target: Node, options: &AlternativeOptions) -> Result<Vec<Path<u32>>, SearchError> {
+    let tree = try!(search(forward, heap, source, Some(target), u32::infinity()));
+    let weight = match tree.get(&target) {
+        Some(&(weight, _)) => weight,
+        None => return Ok(Vec::new())
+    };
+    let (nodes, edges) = tree_path(&tree, target);
+    let mut selection = Selection::new(options, Path {weight: weight, nodes: nodes, edges: edges});
+
+    try!(via_node_alternatives(forward, backward, heap, &mut selection, source, target));
+    if !selection.is_full() {
+        try!(penalty_alternatives(forward, heap, &mut selection, source, target));
+    }
+    Ok(selection.paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::{graph, grid};
+
+    fn nodes(paths: &[Path<u32>]) -> Vec<Vec<Node>> {
+        paths.iter().map(|path| path.nodes.clone()).collect()
+    }
+
+    // three disjoint routes from 0 to 1 over 2, 3 and 4
+    #[test]
+    fn disjoint_routes() {
+        let g = graph(&[(0, 2, 5), (2, 1, 5), (0, 3, 6), (3, 1, 5), (0, 4, 10), (4, 1, 10)]);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        let paths = alternatives(&g, &g.reversed(), &mut heap, 0, 1, &AlternativeOptions::default()).unwrap();
+        // the route over 4 is twice as long as the shortest path
+        assert_eq!(nodes(&paths), vec![vec![0, 2, 1], vec![0, 3, 1]]);
+        assert_eq!(paths[1].weight, 11);
+    }
+
+    // 0 - 1 - 2 - 3 with a short detour 1 - 4 - 2, the alternative shares most of the shortest path
+    #[test]
+    fn too_much_sharing() {
+        let g = graph(&[(0, 1, 10), (1, 2, 1), (2, 3, 10), (1, 4, 1), (4, 2, 1)]);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        let paths = alternatives(&g, &g.reversed(), &mut heap, 0, 3, &AlternativeOptions::default()).unwrap();
+        assert_eq!(nodes(&paths), vec![vec![0, 1, 2, 3]]);
+
+        // the detour also fails the local optimality test
+        let options = AlternativeOptions {max_sharing: 1.0, penalty_iterations: 0, ..AlternativeOptions::default()};
+        let paths = alternatives(&g, &g.reversed(), &mut heap, 0, 3, &options).unwrap();
+        assert_eq!(nodes(&paths), vec![vec![0, 1, 2, 3]]);
+
+        let options = AlternativeOptions {max_sharing: 1.0, local_optimality: 0.0, penalty_iterations: 0, ..AlternativeOptions::default()};
+        let paths = alternatives(&g, &g.reversed(), &mut heap, 0, 3, &options).unwrap();
+        assert_eq!(nodes(&paths), vec![vec![0, 1, 2, 3], vec![0, 1, 4, 2, 3]]);
+    }
+
+    #[test]
+    fn grid_alternatives() {
+        let g = grid(8, 8);
+        let backward = g.reversed();
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        let options = AlternativeOptions::default();
+        for &(source, target) in &[(7, 56), (60, 3), (63, 0)] {
+            let paths = alternatives(&g, &backward, &mut heap, source, target, &options).unwrap();
+            assert!(paths.len() > 1 && paths.len() <= options.max_alternatives + 1);
+            assert_eq!(Some(paths[0].weight), dijkstra(&g, &mut heap, source, target).unwrap());
+            for path in &paths {
+                assert_eq!(path.weight, path_weight(&g, &path.edges));
+                assert_eq!((path.nodes[0], *path.nodes.last().unwrap()), (source, target));
+                assert!(is_simple(path));
+                assert!(path.weight as f64 <= paths[0].weight as f64 * options.max_stretch);
+            }
+            for (index, path) in paths.iter().enumerate().skip(1) {
+                for other in &paths[..index] {
+                    let shared : Vec<Edge> = path.edges.iter().cloned().filter(|edge| other.edges.contains(edge)).collect();
+                    assert!(path_weight(&g, &shared) as f64 <= options.max_sharing * paths[0].weight as f64);
+                }
+            }
+        }
+    }
+
+    // 0 -> 1 -> 2 -> 3 and 0 -> 4 -> 5 -> 3 where 4 -> 6 -> 5 is shorter than 4 -> 5
+    #[test]
+    fn penalty_fallback() {
+        let g = graph(&[(0, 1, 10), (1, 2, 10), (2, 3, 10), (0, 4, 10), (4, 5, 13), (4, 6, 5), (6, 5, 5), (5, 3, 10)]);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        let shortest = Path {weight: 30, nodes: vec![0, 1, 2, 3], edges: vec![find_edge(&g, 0, 1), find_edge(&g, 1, 2), find_edge(&g, 2, 3)]};
+        let options = AlternativeOptions::default();
+        let mut selection = Selection::new(&options, shortest);
+        penalty_alternatives(&g, &mut heap, &mut selection, 0, 3).unwrap();
+        // the second penalty moves the search from 4 -> 6 -> 5 to the direct edge
+        assert_eq!(nodes(&selection.paths), vec![vec![0, 1, 2, 3], vec![0, 4, 6, 5, 3], vec![0, 4, 5, 3]]);
+        assert_eq!(selection.paths[2].weight, 33);
+    }
+
+    #[test]
+    fn unreachable() {
+        let g = graph(&[(0, 1, 1), (2, 1, 1)]);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        assert_eq!(alternatives(&g, &g.reversed(), &mut heap, 0, 2, &AlternativeOptions::default()), Ok(vec![]));
+    }
+}
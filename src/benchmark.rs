@@ -0,0 +1,126 @@
+use graph::{Graph, Node, Edge};
+use search::{WeightedData, SearchStats, SearchVisitor, dijkstra_one_to_all};
+use addressable_heap::AddressableHeap;
+use random::XorShift;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub type Query = (Node, Node);
+
+// source and target chosen uniformly at random
+pub fn random_queries(num_nodes: usize, count: usize, random: &mut XorShift) -> Vec<Query> {
+    (0..count).map(|_| (random.next_below(num_nodes) as Node, random.next_below(num_nodes) as Node)).collect()
+}
+
+// A query whose target is the 2^rank-th node settled by a dijkstra search from source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RankQuery {
+    pub source: Node,
+    pub target: Node,
+    pub rank: u32
+}
+
+// records nodes in the order a one-to-all dijkstra search settles them
+struct SettleOrder {
+    order: Vec<Node>
+}
+
+impl SearchVisitor<u32> for SettleOrder {
+    fn settle(&mut self, node: Node, _: u32) {
+        self.order.push(node);
+    }
+}
+
+// Dijkstra-rank queries for num_sources random sources, see
+// Sanders and Schultes, "Highway Hierarchies Hasten Exact Shortest Path Queries"
+pub fn dijkstra_rank_queries<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(graph: &G, heap: &mut H, num_sources: usize, random: &mut XorShift) -> Vec<RankQuery> {
+    let mut queries = Vec::new();
+    for _ in 0..num_sources {
+        let source = random.next_below(graph.num_nodes()) as Node;
+        let mut visitor = SettleOrder {order: vec![]};
+        dijkstra_one_to_all(graph, heap, source, &mut visitor).unwrap();
+        let order = visitor.order;
+        let mut rank = 0;
+        while (1 << rank) < order.len() {
+            queries.push(RankQuery {source: source, target: order[1 << rank], rank: rank});
+            rank += 1;
+        }
+    }
+    queries
+}
+
+pub struct Measurement {
+    pub name: String,
+    pub queries: usize,
+    pub found: usize,
+    pub stats: SearchStats,
+    pub time: Duration
+}
+
+fn to_millis(duration: &Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1e6
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let queries = self.queries.max(1) as f64;
+        write!(f, "{:<24} {:>6} queries {:>6} found {:>10.1} settled {:>10.1} relaxed {:>10.1} decreased {:>10.4} ms/query",
+               self.name, self.queries, self.found,
+               self.stats.settled as f64 / queries, self.stats.relaxed as f64 / queries,
+               self.stats.decreased as f64 / queries, to_millis(&self.time) / queries)
+    }
+}
+
+// Runs query for every pair and sums up the statistics. The closure returns true
+// if a path was found, so any search algorithm can be measured this way.
+pub fn measure<F: FnMut(Node, Node, &mut SearchStats) -> bool>(name: &str, queries: &[Query], mut query: F) -> Measurement {
+    let mut stats = SearchStats::default();
+    let mut found = 0;
+    let start = Instant::now();
+    for &(source, target) in queries {
+        if query(source, target, &mut stats) {
+            found += 1;
+        }
+    }
+    Measurement {name: String::from(name), queries: queries.len(), found: found, stats: stats, time: start.elapsed()}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use search::*;
+    use addressable_heap::*;
+    use test_util::TestData;
+
+    // 0 -> 1 -> 2 -> 3 -> 4
+    fn path_graph() -> AdjArrayGraph<TestData> {
+        AdjArrayGraph::new((0..4).map(|id| (id, id + 1, TestData {weight: 1})).collect())
+    }
+
+    #[test]
+    fn rank_queries() {
+        let graph = path_graph();
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let mut random = XorShift::new(1);
+        let queries = dijkstra_rank_queries(&graph, &mut heap, 10, &mut random);
+        for query in queries {
+            // on a path the 2^rank-th settled node is 2^rank hops away
+            assert_eq!(query.target - query.source, 1 << query.rank);
+        }
+    }
+
+    #[test]
+    fn measure_dijkstra() {
+        let graph = path_graph();
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let queries = vec![(0, 4), (4, 0), (1, 2)];
+        let measurement = measure("dijkstra", &queries, |source, target, stats| {
+            dijkstra_with_stats(&graph, &mut heap, source, target, stats).unwrap().is_some()
+        });
+        assert_eq!(measurement.queries, 3);
+        assert_eq!(measurement.found, 2);
+        assert_eq!(measurement.stats, SearchStats {settled: 5 + 1 + 2, relaxed: 4 + 0 + 1, decreased: 0});
+    }
+}
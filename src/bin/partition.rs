@@ -0,0 +1,56 @@
+extern crate shortestpath;
+
+use shortestpath::graph_builder::{load_geojson, LoadOptions};
+use shortestpath::graph::Graph;
+use shortestpath::partition::{InertialFlow, cut_edges, write_partition};
+
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process;
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+// Partitions a road network with inertial flow and writes the cells of every node
+// to <network>.partition. Line i holds the cells of internal node id i, which is the
+// id the node gets from load_geojson with the default LoadOptions. Use the id_map of
+// the loaded network to look up the line of an OSM node.
+//
+// partition <network.geojson> <cell size>...
+fn main() {
+    let args : Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        fail(String::from("Usage: partition <network.geojson> <cell size>..."));
+    }
+
+    let cell_sizes : Vec<usize> = args[1..].iter().map(|size| match size.parse() {
+        Ok(size) => size,
+        Err(_) => fail(format!("cell size {} is not a number", size))
+    }).collect();
+    if cell_sizes.windows(2).any(|sizes| sizes[0] > sizes[1]) {
+        fail(String::from("cell sizes have to be increasing"));
+    }
+    let network = match load_geojson(&args[0], &LoadOptions::default()) {
+        Ok(network) => network,
+        Err(error) => fail(format!("could not load {}: {:?}", args[0], error))
+    };
+    println!("{}: {} nodes {} edges", args[0], network.graph.num_nodes(), network.graph.num_edges());
+
+    let partition = InertialFlow::default().partition(&network.graph, &network.coordinates, &cell_sizes);
+    for level in 0..partition.num_levels() {
+        println!("level {}: {} cells, {} cut edges", level, partition.num_cells(level), cut_edges(&network.graph, &partition, level));
+    }
+
+    let path = format!("{}.partition", args[0]);
+    let file = match File::create(&path) {
+        Ok(file) => file,
+        Err(error) => fail(format!("could not create {}: {}", path, error))
+    };
+    if let Err(error) = write_partition(&mut BufWriter::new(file), &partition) {
+        fail(format!("could not write {}: {}", path, error));
+    }
+    println!("wrote {}", path);
+}
@@ -0,0 +1,269 @@
+use addressable_heap::AddressableHeap;
+use graph::{Graph, Node, Edge};
+use search::WeightedData;
+use handle_map::{HandleMap, DenseHandleMap, NOT_INSERTED, REMOVED};
+use weight::Weight;
+
+#[derive(Clone, Copy)]
+struct Position {
+    bucket: u32,
+    index: u32
+}
+
+// Bucket queue for Dial's algorithm. During a dijkstra search all keys in the
+// queue lie in [min, min + max_edge_weight], so max_edge_weight + 1 buckets
+// used as a circular array are enough to store every key in its own bucket.
+pub struct BucketQueue<M: HandleMap = DenseHandleMap> {
+    buckets: Vec<Vec<(u32, u32)>>,
+    // key of the bucket the search for the minimum starts at
+    current: u32,
+    len: usize,
+    // index into positions of every handle in the heap
+    handle_to_index: M,
+    positions: Vec<Position>
+}
+
+// largest weight of any open edge, used to size the BucketQueue. Searches skip closed
+// edges, so their infinite weight never becomes a key.
+pub fn max_edge_weight<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G) -> u32 {
+    let mut max_weight = 0;
+    for node in graph.nodes() {
+        for edge in graph.edges(node) {
+            let weight = graph.data(edge).weight();
+            if weight != u32::infinity() {
+                max_weight = max_weight.max(weight);
+            }
+        }
+    }
+    max_weight
+}
+
+impl BucketQueue {
+    pub fn new(num_handles: usize, max_edge_weight: u32) -> BucketQueue {
+        BucketQueue::with_handle_map(DenseHandleMap::new(num_handles), max_edge_weight)
+    }
+}
+
+impl<M: HandleMap> BucketQueue<M> {
+    pub fn with_handle_map(handle_to_index: M, max_edge_weight: u32) -> BucketQueue<M> {
+        BucketQueue {
+            buckets: (0..max_edge_weight as usize + 1).map(|_| Vec::new()).collect(),
+            current: 0,
+            len: 0,
+            handle_to_index: handle_to_index,
+            positions: vec![]
+        }
+    }
+
+    fn position(&self, handle: u32) -> Position {
+        self.positions[self.handle_to_index.get(handle) as usize]
+    }
+
+    fn set_position(&mut self, handle: u32, position: Position) {
+        if self.in_heap(handle) {
+            let index = self.handle_to_index.get(handle);
+            self.positions[index as usize] = position;
+        } else {
+            let index = self.positions.len() as u32;
+            self.positions.push(position);
+            self.handle_to_index.set(handle, index);
+        }
+    }
+
+    fn bucket_of(&self, key: u32) -> usize {
+        key as usize % self.buckets.len()
+    }
+
+    fn insert(&mut self, handle: u32, key: u32) {
+        assert!(key >= self.current && key - self.current < self.buckets.len() as u32,
+                "Key {} is outside of the range [{}, {}]", key, self.current, self.current as usize + self.buckets.len() - 1);
+        let bucket = self.bucket_of(key);
+        let position = Position {bucket: bucket as u32, index: self.buckets[bucket].len() as u32};
+        self.set_position(handle, position);
+        self.buckets[bucket].push((handle, key));
+    }
+
+    // takes the handle out of its bucket
+    fn unlink(&mut self, handle: u32) -> u32 {
+        let position = self.position(handle);
+        let (_, key) = self.buckets[position.bucket as usize].swap_remove(position.index as usize);
+        if let Some(&(moved, _)) = self.buckets[position.bucket as usize].get(position.index as usize) {
+            let moved_index = self.handle_to_index.get(moved);
+            self.positions[moved_index as usize].index = position.index;
+        }
+        key
+    }
+
+    // offset of the first non-empty bucket starting from current
+    fn first_non_empty(&self) -> Option<u32> {
+        if self.len == 0 {
+            return None;
+        }
+        (0..self.buckets.len() as u32).find(|&offset| !self.buckets[self.bucket_of(self.current + offset)].is_empty())
+    }
+}
+
+impl<M: HandleMap> AddressableHeap<u32> for BucketQueue<M> {
+    type Handle = u32;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn min(&self) -> Option<(Self::Handle, u32)> {
+        self.first_non_empty().and_then(|offset| {
+            self.buckets[self.bucket_of(self.current + offset)].last().cloned()
+        })
+    }
+
+    fn key(&self, handle: Self::Handle) -> u32 {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let position = self.position(handle);
+        self.buckets[position.bucket as usize][position.index as usize].1
+    }
+
+    fn push(&mut self, handle: Self::Handle, key: u32) {
+        self.insert(handle, key);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(Self::Handle, u32)> {
+        match self.first_non_empty() {
+            None => None,
+            Some(offset) => {
+                self.current += offset;
+                let bucket = self.bucket_of(self.current);
+                let (handle, key) = self.buckets[bucket].pop().unwrap();
+                self.handle_to_index.set(handle, REMOVED);
+                self.len -= 1;
+                Some((handle, key))
+            }
+        }
+    }
+
+    fn decrease(&mut self, handle: Self::Handle, key: u32) {
+        if self.key(handle) <= key {
+            return;
+        }
+        self.unlink(handle);
+        self.insert(handle, key);
+    }
+
+    fn increase(&mut self, handle: Self::Handle, key: u32) {
+        if self.key(handle) >= key {
+            return;
+        }
+        self.unlink(handle);
+        self.insert(handle, key);
+    }
+
+    fn remove(&mut self, handle: Self::Handle) -> u32 {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let key = self.unlink(handle);
+        self.handle_to_index.set(handle, REMOVED);
+        self.len -= 1;
+        key
+    }
+
+    fn in_heap(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) < REMOVED
+    }
+
+    fn was_inserted(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) != NOT_INSERTED
+    }
+
+    fn clear(&mut self) {
+        self.handle_to_index.clear();
+        self.positions.clear();
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        self.current = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use search::*;
+    use handle_map::PagedHandleMap;
+    use test_util::{TestData, graph};
+
+    #[test]
+    fn wrap_around() {
+        let mut h = BucketQueue::new(10, 3);
+        h.push(0, 0);
+        h.push(1, 3);
+        h.push(2, 2);
+        assert_eq!(h.pop(), Some((0, 0)));
+        assert_eq!(h.pop(), Some((2, 2)));
+        // uses the bucket that held key 1
+        h.push(3, 5);
+        h.push(4, 4);
+        h.decrease(3, 3);
+        assert_eq!(h.min().map(|(_, key)| key), Some(3));
+        assert_eq!(h.pop().map(|(_, key)| key), Some(3));
+        assert_eq!(h.pop().map(|(_, key)| key), Some(3));
+        assert_eq!(h.pop(), Some((4, 4)));
+        assert_eq!(h.pop(), None);
+    }
+
+    #[test]
+    fn increase_and_remove() {
+        let mut h = BucketQueue::new(10, 3);
+        h.push(0, 0);
+        h.push(1, 1);
+        h.push(2, 2);
+        h.increase(0, 3);
+        assert_eq!(h.remove(1), 1);
+        assert!(!h.in_heap(1));
+        assert_eq!(h.len(), 2);
+        assert_eq!(h.pop(), Some((2, 2)));
+        assert_eq!(h.pop(), Some((0, 3)));
+        assert_eq!(h.pop(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn key_out_of_range() {
+        let mut h = BucketQueue::new(10, 3);
+        h.push(0, 4);
+    }
+
+    #[test]
+    fn paged_handles() {
+        let mut h = BucketQueue::with_handle_map(PagedHandleMap::new(5000), 3);
+        h.push(4000, 2);
+        h.push(3, 1);
+        assert_eq!(h.pop(), Some((3, 1)));
+        assert!(h.in_heap(4000));
+        h.clear();
+        assert!(!h.was_inserted(4000));
+        assert!(!h.was_inserted(3));
+        h.push(3, 0);
+        assert_eq!(h.pop(), Some((3, 0)));
+    }
+
+    // 0 --> 1 ---> 2
+    // |------------^
+    #[test]
+    fn dial_triangle() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (0, 2, TestData {weight: 3})]);
+        assert_eq!(max_edge_weight(&graph), 3);
+        let mut heap = BucketQueue::new(graph.num_nodes(), max_edge_weight(&graph));
+        assert_eq!(dijkstra(&graph, &mut heap, 0, 2), Ok(Some(2)));
+        assert_eq!(dijkstra(&graph, &mut heap, 1, 2), Ok(Some(1)));
+    }
+
+    // the closed edge 0 -> 2 does not need a bucket
+    #[test]
+    fn closed_edge() {
+        let g = graph(&[(0, 1, 2), (1, 2, 2), (0, 2, u32::max_value())]);
+        assert_eq!(max_edge_weight(&g), 2);
+        let mut heap = BucketQueue::new(g.num_nodes(), max_edge_weight(&g));
+        assert_eq!(dijkstra(&g, &mut heap, 0, 2), Ok(Some(4)));
+    }
+}
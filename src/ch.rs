@@ -0,0 +1,396 @@
+use addressable_heap::{AddressableHeap, AddressableBinaryHeap};
+use graph::{Graph, AdjArrayGraph, Node, Edge};
+use search::{WeightedData, SearchError, SearchStats};
+use weight::Weight;
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Witness searches give up after settling this many nodes,
+// a missed witness only adds a shortcut that is not needed.
+const WITNESS_SETTLE_LIMIT : usize = 500;
+
+// middle node of edges that are not shortcuts
+pub const NO_MIDDLE : Node = u32::max_value();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ShortcutData {
+    pub weight: u32,
+    // the contracted node a shortcut skips
+    pub middle: Node
+}
+
+impl WeightedData<u32> for ShortcutData {
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+}
+
+// Contraction hierarchy, see Geisberger et al., "Contraction Hierarchies: Faster and
+// Simpler Hierarchical Routing in Road Networks". Nodes are contracted one after another
+// and shortcuts keep the distances between the remaining nodes. Every shortest path is
+// then found by two searches that only go to more important nodes.
+pub struct ContractionHierarchy {
+    // position of every node in the contraction order
+    rank: Vec<u32>,
+    // edges to nodes of higher rank
+    upward: AdjArrayGraph<ShortcutData>,
+    // edges from nodes of higher rank, reversed so the backward search goes upward as well
+    downward: AdjArrayGraph<ShortcutData>
+}
+
+// Remaining graph during contraction, edges to contracted nodes are removed.
+struct Contraction {
+    outgoing: Vec<Vec<(Node, ShortcutData)>>,
+    incoming: Vec<Vec<(Node, ShortcutData)>>,
+    contracted_neighbors: Vec<u32>,
+    witness_heap: AddressableBinaryHeap<u32>,
+    witness_distances: HashMap<Node, u32>
+}
+
+// keeps only the lighter of two parallel edges
+fn insert_edge(edges: &mut Vec<(Node, ShortcutData)>, target: Node, data: ShortcutData) {
+    match edges.iter().position(|&(other, _)| other == target) {
+        Some(index) => if data.weight < edges[index].1.weight {
+            edges[index].1 = data;
+        },
+        None => edges.push((target, data))
+    }
+}
+
+impl Contraction {
+    fn new<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G) -> Contraction {
+        let num_nodes = graph.num_nodes();
+        let mut outgoing = vec![Vec::new(); num_nodes];
+        let mut incoming = vec![Vec::new(); num_nodes];
+        for node in graph.nodes() {
+            for edge in graph.edges(node) {
+                let target = graph.target(edge);
+                let weight = graph.data(edge).weight();
+                if target == node || weight == u32::infinity() {
+                    continue;
+                }
+                let data = ShortcutData {weight: weight, middle: NO_MIDDLE};
+                insert_edge(&mut outgoing[node as usize], target, data);
+                insert_edge(&mut incoming[target as usize], node, data);
+            }
+        }
+        Contraction {
+            outgoing: outgoing,
+            incoming: incoming,
+            contracted_neighbors: vec![0; num_nodes],
+            witness_heap: AddressableBinaryHeap::new(num_nodes),
+            witness_distances: HashMap::new()
+        }
+    }
+
+    // Dijkstra from source that ignores node and stops once it is beyond max_weight
+    fn witness_search(&mut self, source: Node, node: Node, max_weight: u32) {
+        self.witness_heap.clear();
+        self.witness_distances.clear();
+        self.witness_heap.push(source, 0);
+        while let Some((current, weight)) = self.witness_heap.pop() {
+            self.witness_distances.insert(current, weight);
+            if weight > max_weight || self.witness_distances.len() >= WITNESS_SETTLE_LIMIT {
+                break;
+            }
+            for &(target, data) in &self.outgoing[current as usize] {
+                if target == node {
+                    continue;
+                }
+                let total_weight = weight.saturating_add(data.weight);
+                if self.witness_heap.in_heap(target) {
+                    if total_weight < self.witness_heap.key(target) {
+                        self.witness_heap.decrease(target, total_weight);
+                    }
+                } else if !self.witness_heap.was_inserted(target) {
+                    self.witness_heap.push(target, total_weight);
+                }
+            }
+        }
+    }
+
+    // shortcuts (source, target, data) needed to keep the distances when node is removed
+    fn shortcuts(&mut self, node: Node) -> Result<Vec<(Node, Node, ShortcutData)>, SearchError> {
+        let mut shortcuts = Vec::new();
+        let incoming = self.incoming[node as usize].clone();
+        let max_outgoing = self.outgoing[node as usize].iter().map(|&(_, data)| data.weight).max().unwrap_or(0);
+        for &(source, in_data) in &incoming {
+            self.witness_search(source, node, in_data.weight.saturating_add(max_outgoing));
+            for &(target, out_data) in &self.outgoing[node as usize] {
+                if target == source {
+                    continue;
+                }
+                let weight = match in_data.weight.checked_add(out_data.weight) {
+                    Some(weight) => weight,
+                    None => return Err(SearchError::ShortcutOverflow(source, target))
+                };
+                // nodes still in the heap have a path as well, just maybe not the shortest
+                let witness = self.witness_distances.get(&target).cloned().or_else(|| {
+                    if self.witness_heap.in_heap(target) { Some(self.witness_heap.key(target)) } else { None }
+                });
+                if witness.unwrap_or(u32::infinity()) > weight {
+                    shortcuts.push((source, target, ShortcutData {weight: weight, middle: node}));
+                }
+            }
+        }
+        Ok(shortcuts)
+    }
+
+    // edge difference plus the number of contracted neighbors, which spreads the contraction evenly
+    fn priority(&mut self, node: Node) -> Result<i64, SearchError> {
+        let num_shortcuts = try!(self.shortcuts(node)).len() as i64;
+        let num_edges = (self.incoming[node as usize].len() + self.outgoing[node as usize].len()) as i64;
+        Ok(2 * (num_shortcuts - num_edges) + self.contracted_neighbors[node as usize] as i64)
+    }
+
+    // removes node and returns its neighbors
+    fn contract(&mut self, node: Node) -> Result<Vec<Node>, SearchError> {
+        for (source, target, data) in try!(self.shortcuts(node)) {
+            insert_edge(&mut self.outgoing[source as usize], target, data);
+            insert_edge(&mut self.incoming[target as usize], source, data);
+        }
+
+        let mut neighbors = Vec::new();
+        for &(source, _) in &self.incoming[node as usize] {
+            self.outgoing[source as usize].retain(|&(other, _)| other != node);
+            neighbors.push(source);
+        }
+        for &(target, _) in &self.outgoing[node as usize] {
+            self.incoming[target as usize].retain(|&(other, _)| other != node);
+            neighbors.push(target);
+        }
+        neighbors.sort();
+        neighbors.dedup();
+        for &neighbor in &neighbors {
+            self.contracted_neighbors[neighbor as usize] += 1;
+        }
+        Ok(neighbors)
+    }
+}
+
+fn edges_of<G: Graph<ShortcutData, N=Node, E=Edge>>(graph: &G, node: Node) -> Range<Edge> {
+    // nodes without edges at the end of the order are missing in the graph
+    if (node as usize) < graph.num_nodes() { graph.edges(node) } else { 0..0 }
+}
+
+impl ContractionHierarchy {
+    // Contracts the nodes with the fewest shortcuts first.
+    pub fn new<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G) -> Result<ContractionHierarchy, SearchError> {
+        let mut contraction = Contraction::new(graph);
+        let mut queue : AddressableBinaryHeap<i64> = AddressableBinaryHeap::new(graph.num_nodes());
+        for node in graph.nodes() {
+            let priority = try!(contraction.priority(node));
+            queue.push(node, priority);
+        }
+
+        let mut order = Vec::with_capacity(graph.num_nodes());
+        while let Some((node, key)) = queue.min() {
+            // priorities are only updated for neighbors, check that the node is still the best
+            let priority = try!(contraction.priority(node));
+            if priority > key {
+                queue.increase(node, priority);
+                continue;
+            }
+            queue.pop();
+            order.push(node);
+            for neighbor in try!(contraction.contract(node)) {
+                let priority = try!(contraction.priority(neighbor));
+                queue.update(neighbor, priority);
+            }
+        }
+        ContractionHierarchy::build(graph, contraction, &order)
+    }
+
+    // Contracts the nodes in the given order, the least important nodes come first.
+    pub fn with_order<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, order: &[Node]) -> Result<ContractionHierarchy, SearchError> {
+        assert_eq!(order.len(), graph.num_nodes());
+        let mut contraction = Contraction::new(graph);
+        for &node in order {
+            try!(contraction.contract(node));
+        }
+        ContractionHierarchy::build(graph, contraction, order)
+    }
+
+    // The edges left at a node when it was contracted all lead to nodes of higher rank.
+    fn build<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, contraction: Contraction, order: &[Node]) -> Result<ContractionHierarchy, SearchError> {
+        let mut rank = vec![0; graph.num_nodes()];
+        for (index, &node) in order.iter().enumerate() {
+            rank[node as usize] = index as u32;
+        }
+        let mut upward_edges = Vec::new();
+        let mut downward_edges = Vec::new();
+        for node in graph.nodes() {
+            for &(target, data) in &contraction.outgoing[node as usize] {
+                upward_edges.push((node, target, data));
+            }
+            for &(source, data) in &contraction.incoming[node as usize] {
+                downward_edges.push((node, source, data));
+            }
+        }
+        Ok(ContractionHierarchy {rank: rank, upward: AdjArrayGraph::new(upward_edges), downward: AdjArrayGraph::new(downward_edges)})
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.rank.len()
+    }
+
+    pub fn rank(&self, node: Node) -> u32 {
+        self.rank[node as usize]
+    }
+
+    // nodes from the least to the most important one
+    pub fn order(&self) -> Vec<Node> {
+        let mut order = vec![0; self.rank.len()];
+        for (node, &rank) in self.rank.iter().enumerate() {
+            order[rank as usize] = node as Node;
+        }
+        order
+    }
+
+    pub fn upward(&self) -> &AdjArrayGraph<ShortcutData> {
+        &self.upward
+    }
+
+    pub fn downward(&self) -> &AdjArrayGraph<ShortcutData> {
+        &self.downward
+    }
+
+    // every edge is stored in the graph of its less important node only
+    pub fn num_shortcuts(&self) -> usize {
+        let count = |graph: &AdjArrayGraph<ShortcutData>| graph.nodes().flat_map(|node| graph.edges(node)).filter(|&edge| graph.data(edge).middle != NO_MIDDLE).count();
+        count(&self.upward) + count(&self.downward)
+    }
+}
+
+// Settles every node reachable from source in an upward or downward graph of a hierarchy,
+// edges of nodes for which stop returns true are not relaxed. Returns the settled nodes sorted by id.
+pub fn upward_search<G: Graph<ShortcutData, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>, F: Fn(Node) -> bool>(graph: &G, heap: &mut H, source: Node, stop: F, stats: &mut SearchStats) -> Result<Vec<(Node, u32)>, SearchError> {
+    heap.clear();
+    heap.push(source, 0);
+    let mut settled = Vec::new();
+    while let Some((node, weight)) = heap.pop() {
+        stats.settled += 1;
+        settled.push((node, weight));
+        if stop(node) {
+            continue;
+        }
+        try!(relax_edges(graph, heap, node, weight, stats));
+    }
+    settled.sort();
+    Ok(settled)
+}
+
+fn relax_edges<G: Graph<ShortcutData, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(graph: &G, heap: &mut H, node: Node, weight: u32, stats: &mut SearchStats) -> Result<(), SearchError> {
+    for edge in edges_of(graph, node) {
+        let target = graph.target(edge);
+        let total_weight = match weight.checked_add(graph.data(edge).weight) {
+            Some(total_weight) => total_weight,
+            None => return Err(SearchError::Overflow(node, edge))
+        };
+        stats.relaxed += 1;
+        if heap.in_heap(target) {
+            if total_weight < heap.key(target) {
+                heap.decrease(target, total_weight);
+                stats.decreased += 1;
+            }
+        } else if !heap.was_inserted(target) {
+            heap.push(target, total_weight);
+        }
+    }
+    Ok(())
+}
+
+// Bidirectional upward search, the searches meet at the most important node of the shortest path.
+// Each search stops once its smallest key can not improve the best path anymore.
+pub fn ch_query<H: AddressableHeap<u32, Handle=Node>>(ch: &ContractionHierarchy, forward_heap: &mut H, backward_heap: &mut H, source: Node, target: Node, stats: &mut SearchStats) -> Result<Option<u32>, SearchError> {
+    forward_heap.clear();
+    backward_heap.clear();
+    forward_heap.push(source, 0);
+    backward_heap.push(target, 0);
+    let mut settled = [HashMap::new(), HashMap::new()];
+    let mut best = u32::infinity();
+
+    loop {
+        let forward_min = forward_heap.min().map(|(_, key)| key).filter(|&key| key < best);
+        let backward_min = backward_heap.min().map(|(_, key)| key).filter(|&key| key < best);
+        let direction = match (forward_min, backward_min) {
+            (Some(forward_min), Some(backward_min)) => if forward_min <= backward_min { 0 } else { 1 },
+            (Some(_), None) => 0,
+            (None, Some(_)) => 1,
+            (None, None) => break
+        };
+        let (graph, heap) = if direction == 0 {
+            (&ch.upward, &mut *forward_heap)
+        } else {
+            (&ch.downward, &mut *backward_heap)
+        };
+
+        let (node, weight) = heap.pop().unwrap();
+        stats.settled += 1;
+        settled[direction].insert(node, weight);
+        if let Some(&other_weight) = settled[1 - direction].get(&node) {
+            best = best.min(weight.saturating_add(other_weight));
+        }
+        try!(relax_edges(graph, heap, node, weight, stats));
+    }
+
+    Ok(if best == u32::infinity() { None } else { Some(best) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use search::*;
+    use test_util::{TestData, graph, grid};
+
+    fn check_queries(graph: &AdjArrayGraph<TestData>, ch: &ContractionHierarchy) {
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let mut backward_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let mut stats = SearchStats::default();
+        for source in graph.nodes() {
+            for target in graph.nodes() {
+                let expected = dijkstra(graph, &mut heap, source, target);
+                assert_eq!(ch_query(ch, &mut heap, &mut backward_heap, source, target, &mut stats), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_queries() {
+        let graph = grid(9, 7);
+        let ch = ContractionHierarchy::new(&graph).unwrap();
+        check_queries(&graph, &ch);
+        let mut order = ch.order();
+        order.sort();
+        assert_eq!(order, graph.nodes().collect::<Vec<Node>>());
+    }
+
+    #[test]
+    fn given_order() {
+        let graph = grid(6, 5);
+        let order : Vec<Node> = graph.nodes().rev().collect();
+        let ch = ContractionHierarchy::with_order(&graph, &order).unwrap();
+        assert_eq!(ch.order(), order);
+        assert_eq!(ch.rank(29), 0);
+        check_queries(&graph, &ch);
+    }
+
+    // 0 -> 1 -> 2, contracting 1 first needs a shortcut, 3 is isolated
+    #[test]
+    fn shortcut() {
+        let graph = graph(&[(0, 1, 2), (1, 2, 3), (3, 3, 1)]);
+        let ch = ContractionHierarchy::with_order(&graph, &[1, 3, 0, 2]).unwrap();
+        assert_eq!(ch.num_shortcuts(), 1);
+        let upward = ch.upward();
+        let edges : Vec<(Node, ShortcutData)> = upward.edges(0).map(|edge| (upward.target(edge), *upward.data(edge))).collect();
+        assert_eq!(edges, vec![(2, ShortcutData {weight: 5, middle: 1})]);
+        check_queries(&graph, &ch);
+    }
+
+    #[test]
+    fn shortcut_overflow() {
+        let graph = graph(&[(0, 1, u32::max_value() - 2), (1, 2, 3)]);
+        assert_eq!(ContractionHierarchy::with_order(&graph, &[1, 0, 2]).err(), Some(SearchError::ShortcutOverflow(0, 2)));
+    }
+}
@@ -0,0 +1,175 @@
+use graph::{Graph, Node, Edge};
+use graph_builder::EdgeData;
+use label_setting::{LabelProblem, Stop, label_setting};
+use path::Path;
+use search::{WeightedData, SearchError};
+use weight::Weight;
+
+use std::marker::PhantomData;
+
+pub trait ResourceData: WeightedData<u32> {
+    // consumed by traversing the edge, e.g. distance or toll
+    fn resource(&self) -> u32;
+}
+
+// length in decimeters, e.g. to limit the distance of a route
+impl ResourceData for EdgeData {
+    fn resource(&self) -> u32 {
+        (self.length() * 10.0).round() as u32
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Constrained {
+    // the path with the least weight among those within the budget and the resource it uses
+    Feasible(Path<u32>, u32),
+    // every path to the target exceeds the budget
+    Infeasible,
+    Unreachable
+}
+
+struct Budget<'a, D: 'a, G: 'a> {
+    graph: &'a G,
+    budget: u32,
+    // a label was dropped for exceeding the budget
+    exceeded: bool,
+    data: PhantomData<D>
+}
+
+impl<'a, D: ResourceData, G: Graph<D, N=Node, E=Edge>> LabelProblem for Budget<'a, D, G> {
+    // weight and resource
+    type Label = (u32, u32);
+    type Step = Edge;
+
+    fn dominates(&self, a: &(u32, u32), b: &(u32, u32)) -> bool {
+        a.0 <= b.0 && a.1 <= b.1
+    }
+
+    fn extend(&mut self, node: Node, &(weight, resource): &(u32, u32), successors: &mut Vec<(Node, (u32, u32), Edge)>) -> Result<(), SearchError> {
+        for edge in self.graph.edges(node) {
+            let data = self.graph.data(edge);
+            if data.weight() == u32::infinity() {
+                continue;
+            }
+            let new_weight = match Weight::checked_add(weight, data.weight()) {
+                Some(new_weight) => new_weight,
+                None => return Err(SearchError::Overflow(node, edge))
+            };
+            match resource.checked_add(data.resource()) {
+                Some(new_resource) if new_resource <= self.budget => successors.push((self.graph.target(edge), (new_weight, new_resource), edge)),
+                _ => self.exceeded = true
+            }
+        }
+        Ok(())
+    }
+}
+
+fn reachable<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, source: Node, target: Node) -> bool {
+    let mut visited = vec![false; graph.num_nodes()];
+    let mut stack = vec![source];
+    visited[source as usize] = true;
+    while let Some(node) = stack.pop() {
+        if node == target {
+            return true;
+        }
+        for edge in graph.edges(node) {
+            let head = graph.target(edge);
+            if !visited[head as usize] && graph.data(edge).weight() != u32::infinity() {
+                visited[head as usize] = true;
+                stack.push(head);
+            }
+        }
+    }
+    false
+}
+
+// Shortest path from source to target whose resource consumption stays within budget.
+// Labels are pairs of (weight, resource), labels exceeding the budget are dropped. Labels are
+// settled by weight and then resource, so the first label settled at the target is optimal.
+pub fn constrained_search<D: ResourceData, G: Graph<D, N=Node, E=Edge>>(graph: &G, source: Node, target: Node, budget: u32) -> Result<Constrained, SearchError> {
+    let mut problem = Budget {graph: graph, budget: budget, exceeded: false, data: PhantomData};
+    let labels = try!(label_setting(&mut problem, graph.num_nodes(), source, target, (0, 0), Stop::First));
+    if let Some(label) = labels.target_label() {
+        let (weight, resource) = *labels.label(label);
+        return Ok(Constrained::Feasible(labels.path(label, weight), resource));
+    }
+
+    if problem.exceeded && reachable(graph, source, target) {
+        Ok(Constrained::Infeasible)
+    } else {
+        Ok(Constrained::Unreachable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use random::XorShift;
+    use test_util::{CostData, cost_graph, random_cost_graph, simple_path_costs};
+
+    // the first cost is the weight, the second the resource
+    impl WeightedData<u32> for CostData {
+        fn weight(&self) -> u32 {
+            self.costs[0]
+        }
+    }
+
+    impl ResourceData for CostData {
+        fn resource(&self) -> u32 {
+            self.costs[1]
+        }
+    }
+
+    // least weight of all simple paths within the budget
+    fn brute_force(g: &AdjArrayGraph<CostData>, source: Node, target: Node, budget: u32) -> Option<u32> {
+        simple_path_costs(g, source, target).iter().filter(|costs| costs[1] <= budget).map(|costs| costs[0]).min()
+    }
+
+    #[test]
+    fn toll_road() {
+        // 0 -> 3 over the toll road 1 is fast, over 2 it is free but slow
+        let g = cost_graph(&[(0, 1, 2, 5), (1, 3, 2, 5), (0, 2, 5, 0), (2, 3, 5, 1)]);
+        assert_eq!(constrained_search(&g, 0, 3, 10), Ok(Constrained::Feasible(Path {weight: 4, nodes: vec![0, 1, 3], edges: vec![0, 2]}, 10)));
+        assert_eq!(constrained_search(&g, 0, 3, 9), Ok(Constrained::Feasible(Path {weight: 10, nodes: vec![0, 2, 3], edges: vec![1, 3]}, 1)));
+        assert_eq!(constrained_search(&g, 0, 3, 0), Ok(Constrained::Infeasible));
+        assert_eq!(constrained_search(&g, 0, 0, 0), Ok(Constrained::Feasible(Path {weight: 0, nodes: vec![0], edges: vec![]}, 0)));
+    }
+
+    #[test]
+    fn random_graphs() {
+        let mut random = XorShift::new(7);
+        for _ in 0..20 {
+            let g = random_cost_graph(&mut random, 8, 20);
+            for budget in 0..30 {
+                let expected = brute_force(&g, 0, 7, budget);
+                match constrained_search(&g, 0, 7, budget).unwrap() {
+                    Constrained::Feasible(path, resource) => {
+                        assert_eq!(Some(path.weight), expected);
+                        assert!(resource <= budget);
+                        assert_eq!(path.edges.iter().map(|&edge| g.data(edge).resource()).sum::<u32>(), resource);
+                        assert_eq!(path.edges.iter().map(|&edge| g.data(edge).weight()).sum::<u32>(), path.weight);
+                    },
+                    Constrained::Infeasible => {
+                        assert_eq!(expected, None);
+                        assert!(brute_force(&g, 0, 7, u32::max_value()).is_some());
+                    },
+                    Constrained::Unreachable => assert_eq!(brute_force(&g, 0, 7, u32::max_value()), None)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable() {
+        let g = cost_graph(&[(0, 1, 1, 1), (2, 1, 1, 1), (1, 3, u32::max_value(), 0)]);
+        assert_eq!(constrained_search(&g, 0, 2, 10), Ok(Constrained::Unreachable));
+        assert_eq!(constrained_search(&g, 0, 3, 10), Ok(Constrained::Unreachable));
+    }
+
+    #[test]
+    fn overflow() {
+        let g = cost_graph(&[(0, 1, 1, 1), (1, 2, u32::max_value() - 1, 1)]);
+        assert_eq!(constrained_search(&g, 0, 2, 10), Err(SearchError::Overflow(1, 1)));
+    }
+}
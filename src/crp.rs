@@ -0,0 +1,324 @@
+use addressable_heap::AddressableHeap;
+use graph::{Graph, Node, Edge};
+use partition::MultilevelPartition;
+use search::{WeightedData, SearchError, SearchStats};
+use weight::Weight;
+
+use std::collections::HashMap;
+
+// Customizable Route Planning, see Delling et al., "Customizable Route Planning".
+// The overlay only depends on the topology and the partition. Changing the metric
+// only requires a new customization, which computes the weights between the boundary
+// nodes of every cell bottom up.
+
+// boundary index of a node that is not a boundary node on a level
+const NOT_BOUNDARY : u32 = u32::max_value();
+
+struct OverlayLevel {
+    // boundary nodes of every cell
+    boundary: Vec<Vec<Node>>,
+    // position of every node in the boundary list of its cell
+    boundary_index: Vec<u32>
+}
+
+// Metric independent part of CRP. A node is a boundary node of a level
+// if it has an edge to or from another cell of that level.
+pub struct Overlay {
+    partition: MultilevelPartition,
+    levels: Vec<OverlayLevel>
+}
+
+// Weights between the boundary nodes of every cell for one metric.
+// cliques[level][cell][i * k + j] is the weight from boundary node i to boundary node j
+// using only the inside of the cell, k is the number of boundary nodes of the cell.
+pub struct Metric {
+    cliques: Vec<Vec<Vec<u32>>>
+}
+
+impl Overlay {
+    pub fn new<T, G: Graph<T, N=Node, E=Edge>>(graph: &G, partition: MultilevelPartition) -> Overlay {
+        let mut levels = Vec::with_capacity(partition.num_levels());
+        for level in 0..partition.num_levels() {
+            let mut is_boundary = vec![false; graph.num_nodes()];
+            for node in graph.nodes() {
+                for edge in graph.edges(node) {
+                    let target = graph.target(edge);
+                    if partition.cell(level, node) != partition.cell(level, target) {
+                        is_boundary[node as usize] = true;
+                        is_boundary[target as usize] = true;
+                    }
+                }
+            }
+
+            let mut boundary = vec![Vec::new(); partition.num_cells(level)];
+            let mut boundary_index = vec![NOT_BOUNDARY; graph.num_nodes()];
+            for node in graph.nodes() {
+                if is_boundary[node as usize] {
+                    let ref mut cell_boundary = boundary[partition.cell(level, node) as usize];
+                    boundary_index[node as usize] = cell_boundary.len() as u32;
+                    cell_boundary.push(node);
+                }
+            }
+            levels.push(OverlayLevel {boundary: boundary, boundary_index: boundary_index});
+        }
+        Overlay {partition: partition, levels: levels}
+    }
+
+    pub fn partition(&self) -> &MultilevelPartition {
+        &self.partition
+    }
+
+    pub fn boundary(&self, level: usize, cell: u32) -> &[Node] {
+        &self.levels[level].boundary[cell as usize]
+    }
+
+    // Computes the cliques level by level. Level 0 searches the original graph inside a cell,
+    // higher levels search the cliques of the level below plus the edges between its cells.
+    // The graph has to have the topology the overlay was created for.
+    pub fn customize<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(&self, graph: &G, heap: &mut H) -> Result<Metric, SearchError> {
+        let mut metric = Metric {cliques: Vec::with_capacity(self.levels.len())};
+        let mut distances = HashMap::new();
+        for level in 0..self.levels.len() {
+            let mut level_cliques = Vec::with_capacity(self.levels[level].boundary.len());
+            for (cell, boundary) in self.levels[level].boundary.iter().enumerate() {
+                let k = boundary.len();
+                let mut clique = vec![u32::infinity(); k * k];
+                for (i, &source) in boundary.iter().enumerate() {
+                    try!(self.cell_search(graph, heap, &metric, level, cell as u32, source, &mut distances));
+                    for (j, &target) in boundary.iter().enumerate() {
+                        if let Some(&distance) = distances.get(&target) {
+                            clique[i * k + j] = distance;
+                        }
+                    }
+                }
+                level_cliques.push(clique);
+            }
+            metric.cliques.push(level_cliques);
+        }
+        Ok(metric)
+    }
+
+    // one-to-all search that does not leave the cell, metric has the cliques of all lower levels
+    fn cell_search<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(&self, graph: &G, heap: &mut H, metric: &Metric, level: usize, cell: u32, source: Node, distances: &mut HashMap<Node, u32>) -> Result<(), SearchError> {
+        heap.clear();
+        distances.clear();
+        heap.push(source, 0);
+
+        while let Some((node, weight)) = heap.pop() {
+            distances.insert(node, weight);
+            if level > 0 {
+                try!(self.relax_clique(heap, metric, level - 1, node, weight, true));
+            }
+
+            for edge in graph.edges(node) {
+                let target = graph.target(edge);
+                if self.partition.cell(level, target) != cell {
+                    continue;
+                }
+                // the clique of the lower level already covers edges inside its cells
+                if level > 0 && self.partition.cell(level - 1, target) == self.partition.cell(level - 1, node) {
+                    continue;
+                }
+                let edge_weight = graph.data(edge).weight();
+                if edge_weight == u32::infinity() {
+                    continue;
+                }
+                match weight.checked_add(edge_weight) {
+                    Some(total_weight) => relax(heap, target, total_weight),
+                    None => return Err(SearchError::Overflow(node, edge))
+                };
+            }
+        }
+        Ok(())
+    }
+
+    // Relaxes the clique edges of the cell of node on level. Forward uses the clique
+    // edges leaving node, backward the ones entering it.
+    fn relax_clique<H: AddressableHeap<u32, Handle=Node>>(&self, heap: &mut H, metric: &Metric, level: usize, node: Node, weight: u32, forward: bool) -> Result<usize, SearchError> {
+        let cell = self.partition.cell(level, node) as usize;
+        let ref boundary = self.levels[level].boundary[cell];
+        let ref clique = metric.cliques[level][cell];
+        let k = boundary.len();
+        let index = self.levels[level].boundary_index[node as usize] as usize;
+        let mut relaxed = 0;
+        for (other_index, &other) in boundary.iter().enumerate() {
+            let shortcut = if forward { clique[index * k + other_index] } else { clique[other_index * k + index] };
+            if other == node || shortcut == u32::infinity() {
+                continue;
+            }
+            match weight.checked_add(shortcut) {
+                Some(total_weight) => relax(heap, other, total_weight),
+                None => return Err(if forward { SearchError::ShortcutOverflow(node, other) } else { SearchError::ShortcutOverflow(other, node) })
+            };
+            relaxed += 1;
+        }
+        Ok(relaxed)
+    }
+
+    // the coarsest level on which the cell of node contains neither source nor target
+    fn query_level(&self, node: Node, source: Node, target: Node) -> Option<usize> {
+        (0..self.partition.num_levels()).rev().find(|&level| {
+            let cell = self.partition.cell(level, node);
+            cell != self.partition.cell(level, source) && cell != self.partition.cell(level, target)
+        })
+    }
+}
+
+// pushes or decreases, settled nodes are ignored
+fn relax<H: AddressableHeap<u32, Handle=Node>>(heap: &mut H, node: Node, weight: u32) -> bool {
+    if heap.in_heap(node) {
+        if weight < heap.key(node) {
+            heap.decrease(node, weight);
+            return true;
+        }
+    } else if !heap.was_inserted(node) {
+        heap.push(node, weight);
+        return true;
+    }
+    false
+}
+
+// Bidirectional search on the overlay, backward has to be the reversed forward graph.
+// Nodes in the level 0 cells of source and target use the original edges, every other
+// node only uses the cliques and the outgoing edges of the coarsest cell that contains
+// neither source nor target.
+pub fn crp_query<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(forward: &G, backward: &G, overlay: &Overlay, metric: &Metric, forward_heap: &mut H, backward_heap: &mut H, source: Node, target: Node, stats: &mut SearchStats) -> Result<Option<u32>, SearchError> {
+    forward_heap.clear();
+    backward_heap.clear();
+    forward_heap.push(source, 0);
+    backward_heap.push(target, 0);
+    let mut settled = [HashMap::new(), HashMap::new()];
+    let mut best = u32::infinity();
+
+    loop {
+        let (forward_min, backward_min) = match (forward_heap.min(), backward_heap.min()) {
+            (Some((_, forward_min)), Some((_, backward_min))) => (forward_min, backward_min),
+            _ => break
+        };
+        if forward_min.saturating_add(backward_min) >= best {
+            break;
+        }
+
+        let is_forward = forward_min <= backward_min;
+        let (graph, heap, other_heap, direction) = if is_forward {
+            (forward, &mut *forward_heap, &mut *backward_heap, 0)
+        } else {
+            (backward, &mut *backward_heap, &mut *forward_heap, 1)
+        };
+
+        let (node, weight) = heap.pop().unwrap();
+        stats.settled += 1;
+        settled[direction].insert(node, weight);
+        if let Some(other_weight) = tentative_weight(&*other_heap, &settled[1 - direction], node) {
+            best = best.min(weight.saturating_add(other_weight));
+        }
+
+        let level = overlay.query_level(node, source, target);
+        let mut touched = Vec::new();
+        if let Some(level) = level {
+            stats.relaxed += try!(overlay.relax_clique(heap, metric, level, node, weight, is_forward));
+            touched.extend_from_slice(overlay.boundary(level, overlay.partition.cell(level, node)));
+        }
+        for edge in graph.edges(node) {
+            let edge_target = graph.target(edge);
+            if let Some(level) = level {
+                if overlay.partition.cell(level, edge_target) == overlay.partition.cell(level, node) {
+                    continue;
+                }
+            }
+            let edge_weight = graph.data(edge).weight();
+            if edge_weight == u32::infinity() {
+                continue;
+            }
+            let total_weight = match weight.checked_add(edge_weight) {
+                Some(total_weight) => total_weight,
+                None => return Err(SearchError::Overflow(node, edge))
+            };
+            stats.relaxed += 1;
+            let was_in_heap = heap.in_heap(edge_target);
+            if relax(heap, edge_target, total_weight) && was_in_heap {
+                stats.decreased += 1;
+            }
+            touched.push(edge_target);
+        }
+
+        // a path is found wherever both searches meet
+        for &touched_node in &touched {
+            if !heap.in_heap(touched_node) {
+                continue;
+            }
+            if let Some(other_weight) = tentative_weight(&*other_heap, &settled[1 - direction], touched_node) {
+                best = best.min(heap.key(touched_node).saturating_add(other_weight));
+            }
+        }
+    }
+
+    Ok(if best == u32::infinity() { None } else { Some(best) })
+}
+
+fn tentative_weight<H: AddressableHeap<u32, Handle=Node>>(heap: &H, settled: &HashMap<Node, u32>, node: Node) -> Option<u32> {
+    if heap.in_heap(node) {
+        Some(heap.key(node))
+    } else {
+        settled.get(&node).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressable_heap::*;
+    use graph::*;
+    use search::*;
+    use test_util::{TestData, seeded_grid};
+
+    fn check_queries(graph: &AdjArrayGraph<TestData>, overlay: &Overlay) {
+        let backward = graph.reversed();
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let mut backward_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let metric = overlay.customize(graph, &mut heap).unwrap();
+
+        let mut dijkstra_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let mut dijkstra_stats = SearchStats::default();
+        let mut crp_stats = SearchStats::default();
+        for source in graph.nodes() {
+            for target in graph.nodes() {
+                let expected = dijkstra_with_stats(graph, &mut dijkstra_heap, source, target, &mut dijkstra_stats).unwrap();
+                assert_eq!(crp_query(graph, &backward, overlay, &metric, &mut heap, &mut backward_heap, source, target, &mut crp_stats), Ok(expected));
+            }
+        }
+        assert!(crp_stats.settled < dijkstra_stats.settled);
+    }
+
+    #[test]
+    fn grid_queries() {
+        let (graph, coordinates) = seeded_grid(9, 8, 0);
+        let overlay = Overlay::new(&graph, MultilevelPartition::from_coordinates(&coordinates, &[6, 24]));
+        check_queries(&graph, &overlay);
+    }
+
+    #[test]
+    fn new_metric() {
+        let (graph, coordinates) = seeded_grid(9, 8, 0);
+        let overlay = Overlay::new(&graph, MultilevelPartition::from_coordinates(&coordinates, &[4, 12, 36]));
+        // same topology with different weights
+        let (graph, _) = seeded_grid(9, 8, 3);
+        check_queries(&graph, &overlay);
+    }
+
+    // 0 --> 1 | 2 --> 3
+    #[test]
+    fn unreachable_cell() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (2, 3, TestData {weight: 1}), (3, 2, TestData {weight: 1})]);
+        let overlay = Overlay::new(&graph, MultilevelPartition::new(vec![vec![0, 0, 1, 1]]));
+        assert!(overlay.boundary(0, 0).is_empty());
+        let backward = graph.reversed();
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(4);
+        let mut backward_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(4);
+        let metric = overlay.customize(&graph, &mut heap).unwrap();
+        let mut stats = SearchStats::default();
+        assert_eq!(crp_query(&graph, &backward, &overlay, &metric, &mut heap, &mut backward_heap, 0, 3, &mut stats), Ok(None));
+        assert_eq!(crp_query(&graph, &backward, &overlay, &metric, &mut heap, &mut backward_heap, 3, 2, &mut stats), Ok(Some(1)));
+        assert_eq!(crp_query(&graph, &backward, &overlay, &metric, &mut heap, &mut backward_heap, 2, 2, &mut stats), Ok(Some(0)));
+    }
+}
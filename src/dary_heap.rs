@@ -0,0 +1,217 @@
+use addressable_heap::AddressableHeap;
+use handle_map::{HandleMap, DenseHandleMap, NOT_INSERTED, REMOVED};
+
+// a 4-ary tree is flatter than a binary tree and
+// all children of a node share a cache line
+const ARITY : usize = 4;
+
+#[derive(Clone, Copy)]
+struct HeapElement<Key: Copy> {
+    key: Key,
+    handle: u32
+}
+
+pub struct AddressableQuaternaryHeap<Key: Copy, M: HandleMap = DenseHandleMap> {
+    tree: Vec<HeapElement<Key>>,
+    handle_to_index: M
+}
+
+impl<Key: Copy + Ord> AddressableQuaternaryHeap<Key> {
+    pub fn new(num_handles: usize) -> AddressableQuaternaryHeap<Key> {
+        AddressableQuaternaryHeap::with_handle_map(DenseHandleMap::new(num_handles))
+    }
+}
+
+impl<Key: Copy + Ord, M: HandleMap> AddressableQuaternaryHeap<Key, M> {
+    pub fn with_handle_map(handle_to_index: M) -> AddressableQuaternaryHeap<Key, M> {
+        AddressableQuaternaryHeap {tree: vec![], handle_to_index: handle_to_index}
+    }
+
+    fn update_handle(&mut self, index: usize) {
+        let handle = self.tree[index].handle;
+        self.handle_to_index.set(handle, index as u32);
+    }
+
+    fn parent(index: usize) -> usize {
+        (index - 1) / ARITY
+    }
+
+    fn first_child(index: usize) -> usize {
+        index * ARITY + 1
+    }
+
+    fn heap_up(&mut self, start_index: usize) {
+        let mut index = start_index;
+        while index > 0 {
+            let parent_index = Self::parent(index);
+            if self.tree[index].key >= self.tree[parent_index].key {
+                break;
+            }
+            self.tree.swap(index, parent_index);
+            self.update_handle(index);
+            self.update_handle(parent_index);
+            index = parent_index;
+        }
+    }
+
+    fn heap_down(&mut self, start_index: usize) {
+        let mut index = start_index;
+        loop {
+            let first = Self::first_child(index);
+            if first >= self.tree.len() {
+                break;
+            }
+            let last = (first + ARITY).min(self.tree.len());
+            let mut min_index = first;
+            for child in first + 1..last {
+                if self.tree[child].key < self.tree[min_index].key {
+                    min_index = child;
+                }
+            }
+            if self.tree[min_index].key >= self.tree[index].key {
+                break;
+            }
+            self.tree.swap(index, min_index);
+            self.update_handle(index);
+            self.update_handle(min_index);
+            index = min_index;
+        }
+    }
+}
+
+impl<Key: Copy + Ord, M: HandleMap> AddressableHeap<Key> for AddressableQuaternaryHeap<Key, M> {
+    type Handle = u32;
+
+    fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    fn min(&self) -> Option<(Self::Handle, Key)> {
+        self.tree.first().map(|top| (top.handle, top.key))
+    }
+
+    fn key(&self, handle: Self::Handle) -> Key {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        self.tree[self.handle_to_index.get(handle) as usize].key
+    }
+
+    fn push(&mut self, handle: Self::Handle, key: Key) {
+        let index = self.tree.len();
+        self.handle_to_index.set(handle, index as u32);
+        self.tree.push(HeapElement {key: key, handle: handle});
+        self.heap_up(index);
+    }
+
+    fn pop(&mut self) -> Option<(Self::Handle, Key)> {
+        if self.tree.is_empty() {
+            return None;
+        }
+
+        let element = self.tree.swap_remove(0);
+        if !self.tree.is_empty() {
+            self.update_handle(0);
+            self.heap_down(0);
+        }
+        self.handle_to_index.set(element.handle, REMOVED);
+        Some((element.handle, element.key))
+    }
+
+    fn decrease(&mut self, handle: Self::Handle, key: Key) {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle) as usize;
+        if self.tree[index].key <= key {
+            return;
+        }
+        self.tree[index].key = key;
+        self.heap_up(index);
+    }
+
+    fn increase(&mut self, handle: Self::Handle, key: Key) {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle) as usize;
+        if self.tree[index].key >= key {
+            return;
+        }
+        self.tree[index].key = key;
+        self.heap_down(index);
+    }
+
+    fn remove(&mut self, handle: Self::Handle) -> Key {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle) as usize;
+        let element = self.tree.swap_remove(index);
+        if index < self.tree.len() {
+            let moved = self.tree[index].handle;
+            self.update_handle(index);
+            self.heap_up(index);
+            if self.handle_to_index.get(moved) as usize == index {
+                self.heap_down(index);
+            }
+        }
+        self.handle_to_index.set(handle, REMOVED);
+        element.key
+    }
+
+    fn in_heap(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) < REMOVED
+    }
+
+    fn was_inserted(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) != NOT_INSERTED
+    }
+
+    fn clear(&mut self) {
+        self.handle_to_index.clear();
+        self.tree.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressable_heap::AddressableHeap;
+
+    #[test]
+    fn push_pop_sorted() {
+        let mut h : AddressableQuaternaryHeap<u32> = AddressableQuaternaryHeap::new(20);
+        let keys = [7, 3, 19, 0, 4, 4, 12, 9, 1, 15, 2];
+        for (handle, &key) in keys.iter().enumerate() {
+            h.push(handle as u32, key);
+        }
+        let mut sorted = keys.to_vec();
+        sorted.sort();
+        for key in sorted {
+            assert_eq!(h.pop().map(|(_, k)| k), Some(key));
+        }
+        assert_eq!(h.pop(), None);
+    }
+
+    #[test]
+    fn decrease_key() {
+        let mut h : AddressableQuaternaryHeap<u32> = AddressableQuaternaryHeap::new(10);
+        for handle in 0..10 {
+            h.push(handle, 10 + handle);
+        }
+        h.decrease(7, 1);
+        assert_eq!(h.min(), Some((7, 1)));
+        // should be a noop
+        h.decrease(3, 20);
+        assert_eq!(h.key(3), 13);
+        assert_eq!(h.pop(), Some((7, 1)));
+        assert_eq!(h.pop(), Some((0, 10)));
+    }
+
+    #[test]
+    fn increase_and_remove() {
+        let mut h : AddressableQuaternaryHeap<u32> = AddressableQuaternaryHeap::new(10);
+        for handle in 0..10 {
+            h.push(handle, handle);
+        }
+        h.increase(0, 20);
+        assert_eq!(h.remove(5), 5);
+        assert!(!h.in_heap(5));
+        assert_eq!(h.remove(1), 1);
+        let keys : Vec<u32> = (0..8).map(|_| h.pop().unwrap().1).collect();
+        assert_eq!(keys, vec![2, 3, 4, 6, 7, 8, 9, 20]);
+    }
+}
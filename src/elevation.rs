@@ -0,0 +1,385 @@
+use wgs84::{WGS84, haversine};
+
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::path;
+
+// Heights in meters sampled on a regular grid of lon/lat coordinates, voids are NaN
+pub struct ElevationGrid {
+    // center of the north west sample
+    north: f64,
+    west: f64,
+    // distance between samples in degrees
+    cell_size: f64,
+    rows: usize,
+    columns: usize,
+    // row major from north to south
+    heights: Vec<f32>
+}
+
+impl ElevationGrid {
+    pub fn new(north: f64, west: f64, cell_size: f64, rows: usize, columns: usize, heights: Vec<f32>) -> ElevationGrid {
+        assert!(rows >= 2 && columns >= 2);
+        assert_eq!(heights.len(), rows * columns);
+        ElevationGrid {north: north, west: west, cell_size: cell_size, rows: rows, columns: columns, heights: heights}
+    }
+
+    fn height(&self, row: usize, column: usize) -> f64 {
+        self.heights[row * self.columns + column] as f64
+    }
+
+    // bilinear interpolation of the four surrounding samples, None outside of the grid or next to a void
+    pub fn elevation(&self, position: &WGS84) -> Option<f64> {
+        let x = (position.lon - self.west) / self.cell_size;
+        let y = (self.north - position.lat) / self.cell_size;
+        if !(x >= 0.0 && y >= 0.0 && x <= (self.columns - 1) as f64 && y <= (self.rows - 1) as f64) {
+            return None;
+        }
+        let column = (x.floor() as usize).min(self.columns - 2);
+        let row = (y.floor() as usize).min(self.rows - 2);
+        let dx = x - column as f64;
+        let dy = y - row as f64;
+
+        let samples = [(row, column, (1.0 - dx) * (1.0 - dy)), (row, column + 1, dx * (1.0 - dy)),
+                       (row + 1, column, (1.0 - dx) * dy), (row + 1, column + 1, dx * dy)];
+        let mut height = 0.0;
+        for &(row, column, share) in &samples {
+            if share > 0.0 {
+                let sample = self.height(row, column);
+                if sample.is_nan() {
+                    return None;
+                }
+                height += share * sample;
+            }
+        }
+        Some(height)
+    }
+}
+
+// A set of grids, e.g. one per SRTM tile, queried in the order they were added
+pub struct Elevation {
+    grids: Vec<ElevationGrid>
+}
+
+impl Elevation {
+    pub fn new() -> Elevation {
+        Elevation {grids: Vec::new()}
+    }
+
+    pub fn push(&mut self, grid: ElevationGrid) {
+        self.grids.push(grid);
+    }
+
+    pub fn elevation(&self, position: &WGS84) -> Option<f64> {
+        self.grids.iter().filter_map(|grid| grid.elevation(position)).next()
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+const HGT_VOID : i16 = -32768;
+
+// An SRTM tile covers one degree, its south west corner is given in whole degrees. It consists of
+// 1201x1201 (3 arc seconds) or 3601x3601 (1 arc second) big endian 16 bit heights from north to
+// south, the outermost rows and columns overlap with the neighboring tiles.
+pub fn read_hgt<R: Read>(mut reader: R, south: i32, west: i32) -> io::Result<ElevationGrid> {
+    let mut bytes = Vec::new();
+    try!(reader.read_to_end(&mut bytes));
+    let size = ((bytes.len() / 2) as f64).sqrt().round() as usize;
+    if size < 2 || size * size * 2 != bytes.len() {
+        return Err(invalid_data("hgt tile is not a square of 16 bit samples"));
+    }
+    let heights = bytes.chunks(2).map(|sample| {
+        match ((sample[0] as u16) << 8 | sample[1] as u16) as i16 {
+            HGT_VOID => ::std::f32::NAN,
+            height => height as f32
+        }
+    }).collect();
+    Ok(ElevationGrid::new((south + 1) as f64, west as f64, 1.0 / (size - 1) as f64, size, size, heights))
+}
+
+// south west corner of a tile named like N43E007.hgt
+fn hgt_origin(name: &str) -> Option<(i32, i32)> {
+    if name.len() < 7 || !name.is_char_boundary(7) {
+        return None;
+    }
+    let (latitude, longitude) = (&name[0..3], &name[3..7]);
+    let south = match (&latitude[..1], latitude[1..].parse::<i32>()) {
+        ("N", Ok(degrees)) | ("n", Ok(degrees)) => degrees,
+        ("S", Ok(degrees)) | ("s", Ok(degrees)) => -degrees,
+        _ => return None
+    };
+    let west = match (&longitude[..1], longitude[1..].parse::<i32>()) {
+        ("E", Ok(degrees)) | ("e", Ok(degrees)) => degrees,
+        ("W", Ok(degrees)) | ("w", Ok(degrees)) => -degrees,
+        _ => return None
+    };
+    Some((south, west))
+}
+
+// reads an SRTM tile, its position is taken from the file name
+pub fn load_hgt(path: &String) -> io::Result<ElevationGrid> {
+    let origin = path::Path::new(path).file_name().and_then(|name| name.to_str()).and_then(hgt_origin);
+    match origin {
+        Some((south, west)) => read_hgt(BufReader::new(try!(File::open(path))), south, west),
+        None => Err(invalid_data("hgt file name does not name a tile like N43E007.hgt"))
+    }
+}
+
+// Reads an ESRI ASCII grid in WGS84 coordinates. The header has the keys ncols, nrows,
+// xllcorner or xllcenter, yllcorner or yllcenter, cellsize and optionally nodata_value,
+// followed by the heights from north to south.
+pub fn read_ascii_grid<R: BufRead>(reader: R) -> io::Result<ElevationGrid> {
+    let mut columns = None;
+    let mut rows = None;
+    let mut west = None;
+    let mut south = None;
+    // the lower left coordinate is either the corner or the center of the cell
+    let mut west_corner = false;
+    let mut south_corner = false;
+    let mut cell_size = None;
+    let mut no_data = None;
+    let mut heights = Vec::new();
+
+    for line in reader.lines() {
+        let line = try!(line);
+        let mut fields = line.split_whitespace();
+        if line.trim().starts_with(char::is_alphabetic) {
+            let key = fields.next().unwrap().to_lowercase();
+            let value = try!(fields.next().and_then(|value| value.parse::<f64>().ok()).ok_or_else(|| invalid_data("header value is not a number")));
+            match key.as_ref() {
+                "ncols" => columns = Some(value as usize),
+                "nrows" => rows = Some(value as usize),
+                "xllcorner" | "xllcenter" => {
+                    west = Some(value);
+                    west_corner = key == "xllcorner";
+                },
+                "yllcorner" | "yllcenter" => {
+                    south = Some(value);
+                    south_corner = key == "yllcorner";
+                },
+                "cellsize" => cell_size = Some(value),
+                "nodata_value" => no_data = Some(value as f32),
+                _ => return Err(invalid_data("unknown header key"))
+            }
+        } else {
+            for field in fields {
+                let height = try!(field.parse::<f32>().map_err(|_| invalid_data("height is not a number")));
+                heights.push(if Some(height) == no_data { ::std::f32::NAN } else { height });
+            }
+        }
+    }
+
+    match (rows, columns, west, south, cell_size) {
+        (Some(rows), Some(columns), Some(west), Some(south), Some(cell_size)) => {
+            if rows < 2 || columns < 2 || heights.len() != rows * columns {
+                return Err(invalid_data("number of heights does not match the header"));
+            }
+            // samples are at the centers of the cells
+            let west = if west_corner { west + cell_size / 2.0 } else { west };
+            let south = if south_corner { south + cell_size / 2.0 } else { south };
+            Ok(ElevationGrid::new(south + (rows - 1) as f64 * cell_size, west, cell_size, rows, columns, heights))
+        },
+        _ => Err(invalid_data("incomplete header"))
+    }
+}
+
+pub fn load_ascii_grid(path: &String) -> io::Result<ElevationGrid> {
+    read_ascii_grid(BufReader::new(try!(File::open(path))))
+}
+
+// How ascent and descent change the travel time of an edge
+pub struct ElevationProfile {
+    // seconds added per meter of ascent
+    pub ascent: f64,
+    // seconds saved per meter of descent
+    pub descent: f64,
+    // share of the flat travel time that remains at least
+    pub min_factor: f64
+}
+
+impl Default for ElevationProfile {
+    // travel times stay as they are, e.g. for cars
+    fn default() -> ElevationProfile {
+        ElevationProfile {ascent: 0.0, descent: 0.0, min_factor: 1.0}
+    }
+}
+
+impl ElevationProfile {
+    pub fn bicycle() -> ElevationProfile {
+        ElevationProfile {ascent: 6.0, descent: 1.5, min_factor: 0.5}
+    }
+
+    // Naismith's rule of one hour per 600 m of ascent
+    pub fn hiking() -> ElevationProfile {
+        ElevationProfile {ascent: 6.0, descent: 0.0, min_factor: 1.0}
+    }
+
+    // weight in deciseconds adjusted by ascent and descent in meters
+    pub fn adjust(&self, weight: u32, ascent: f64, descent: f64) -> u32 {
+        if weight == u32::max_value() {
+            return weight;
+        }
+        let flat = weight as f64;
+        let adjusted = flat + (ascent * self.ascent - descent * self.descent) * 10.0;
+        adjusted.max(flat * self.min_factor).round().min((u32::max_value() - 1) as f64) as u32
+    }
+}
+
+// points closer than this along a line are not sampled in between
+const SAMPLE_DISTANCE : f64 = 30.0;
+
+// Total ascent and descent in meters along a line. Long segments are sampled in between so
+// hills between shape points are not missed, positions without elevation are skipped.
+pub fn ascent_descent(elevation: &Elevation, line: &[WGS84]) -> (f64, f64) {
+    let mut ascent = 0.0;
+    let mut descent = 0.0;
+    let mut previous : Option<f64> = None;
+    for (index, start) in line.iter().enumerate() {
+        // every segment by its own length, the segments of a compressed edge can differ a lot
+        let samples = match line.get(index + 1) {
+            Some(end) => (haversine(start, end) / SAMPLE_DISTANCE).ceil().max(1.0) as usize,
+            None => 1
+        };
+        for sample in 0..samples {
+            let position = match line.get(index + 1) {
+                Some(end) => {
+                    let t = sample as f64 / samples as f64;
+                    WGS84 {lon: start.lon + (end.lon - start.lon) * t, lat: start.lat + (end.lat - start.lat) * t}
+                },
+                None => *start
+            };
+            if let Some(height) = elevation.elevation(&position) {
+                if let Some(previous) = previous {
+                    if height > previous {
+                        ascent += height - previous;
+                    } else {
+                        descent += previous - height;
+                    }
+                }
+                previous = Some(height);
+            }
+        }
+    }
+    (ascent, descent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rises by one meter per 0.001 degrees to the east and two to the north
+    fn slope() -> ElevationGrid {
+        let mut heights = Vec::new();
+        for row in 0..11 {
+            for column in 0..11 {
+                heights.push((2 * (10 - row) + column) as f32);
+            }
+        }
+        ElevationGrid::new(43.01, 7.0, 0.001, 11, 11, heights)
+    }
+
+    #[test]
+    fn bilinear() {
+        let grid = slope();
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.0, lat: 43.01}), Some(20.0));
+        assert!((grid.elevation(&WGS84 {lon: 7.01, lat: 43.0}).unwrap() - 10.0).abs() < 1e-6);
+        assert!((grid.elevation(&WGS84 {lon: 7.0025, lat: 43.0055}).unwrap() - 13.5).abs() < 1e-6);
+        assert_eq!(grid.elevation(&WGS84 {lon: 6.999, lat: 43.005}), None);
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.005, lat: 43.011}), None);
+
+        let mut heights = vec![1.0; 4];
+        heights[3] = ::std::f32::NAN;
+        let voids = ElevationGrid::new(1.0, 0.0, 1.0, 2, 2, heights);
+        assert_eq!(voids.elevation(&WGS84 {lon: 0.5, lat: 0.5}), None);
+
+        let mut elevation = Elevation::new();
+        elevation.push(voids);
+        elevation.push(ElevationGrid::new(1.0, 0.0, 1.0, 2, 2, vec![5.0; 4]));
+        assert_eq!(elevation.elevation(&WGS84 {lon: 0.5, lat: 0.5}), Some(5.0));
+        assert_eq!(elevation.elevation(&WGS84 {lon: 0.0, lat: 1.0}), Some(1.0));
+    }
+
+    #[test]
+    fn hgt() {
+        // 3x3 samples from north west to south east, the center is a void
+        let samples : Vec<i16> = vec![100, 200, 300, 400, HGT_VOID, 600, 700, 800, -10];
+        let mut bytes = Vec::new();
+        for sample in samples {
+            bytes.push((sample >> 8) as u8);
+            bytes.push(sample as u8);
+        }
+        let grid = read_hgt(&bytes[..], 43, 7).unwrap();
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.0, lat: 44.0}), Some(100.0));
+        assert_eq!(grid.elevation(&WGS84 {lon: 8.0, lat: 43.0}), Some(-10.0));
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.25, lat: 44.0}), Some(150.0));
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.25, lat: 43.75}), None);
+        assert_eq!(read_hgt(&bytes[..5], 43, 7).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(hgt_origin("N43E007.hgt"), Some((43, 7)));
+        assert_eq!(hgt_origin("S12W077.hgt"), Some((-12, -77)));
+        assert_eq!(hgt_origin("X43E007.hgt"), None);
+        assert_eq!(hgt_origin("N43"), None);
+        assert_eq!(load_hgt(&String::from("data/sample.geojson")).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn ascii_grid() {
+        let text = "ncols 3\nnrows 2\nxllcorner 7.0\nyllcorner 43.0\ncellsize 0.5\nNODATA_value -9999\n1 2 3\n4 5 -9999\n";
+        let grid = read_ascii_grid(text.as_bytes()).unwrap();
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.25, lat: 43.75}), Some(1.0));
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.25, lat: 43.25}), Some(4.0));
+        assert_eq!(grid.elevation(&WGS84 {lon: 7.5, lat: 43.5}), Some(3.0));
+        assert_eq!(grid.elevation(&WGS84 {lon: 8.0, lat: 43.5}), None);
+
+        let centered = read_ascii_grid("ncols 2\nnrows 2\nxllcenter 7.0\nyllcenter 43.0\ncellsize 1\n1 2\n3 4\n".as_bytes()).unwrap();
+        assert_eq!(centered.elevation(&WGS84 {lon: 7.0, lat: 43.0}), Some(3.0));
+
+        for text in &["ncols 2\nnrows 2\nxllcenter 7\nyllcenter 43\ncellsize 1\n1 2 3\n", "ncols 2\nnrows 2\n1 2\n3 4\n", "ncols two\n"] {
+            assert_eq!(read_ascii_grid(text.as_bytes()).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn profiles() {
+        let car = ElevationProfile::default();
+        assert_eq!(car.adjust(100, 50.0, 0.0), 100);
+        let bicycle = ElevationProfile::bicycle();
+        assert_eq!(bicycle.adjust(100, 10.0, 0.0), 700);
+        assert_eq!(bicycle.adjust(100, 0.0, 2.0), 70);
+        assert_eq!(bicycle.adjust(100, 0.0, 100.0), 50);
+        assert_eq!(bicycle.adjust(u32::max_value(), 10.0, 0.0), u32::max_value());
+    }
+
+    #[test]
+    fn line_ascent_descent() {
+        let mut elevation = Elevation::new();
+        elevation.push(slope());
+        // east 10 m up, then south 20 m down
+        let line = [WGS84 {lon: 7.0, lat: 43.01}, WGS84 {lon: 7.01, lat: 43.01}, WGS84 {lon: 7.01, lat: 43.0}];
+        let (ascent, descent) = ascent_descent(&elevation, &line);
+        assert!((ascent - 10.0).abs() < 1e-6);
+        assert!((descent - 20.0).abs() < 1e-6);
+    }
+
+    // many short segments followed by a long one over a ridge 100 m high at lon 7.005
+    #[test]
+    fn uneven_segments() {
+        let mut heights = Vec::new();
+        for _ in 0..11 {
+            for column in 0..11 {
+                heights.push(if column == 5 { 100.0 } else { 0.0 });
+            }
+        }
+        let mut elevation = Elevation::new();
+        elevation.push(ElevationGrid::new(43.01, 7.0, 0.001, 11, 11, heights));
+        let mut line : Vec<WGS84> = (0..10).map(|step| WGS84 {lon: 7.0 + step as f64 * 0.00001, lat: 43.005}).collect();
+        line.push(WGS84 {lon: 7.01, lat: 43.005});
+        let (ascent, descent) = ascent_descent(&elevation, &line);
+        assert!(ascent > 50.0);
+        assert!((ascent - descent).abs() < 1e-6);
+    }
+}
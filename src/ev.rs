@@ -0,0 +1,400 @@
+use geojson::{GeoJson, Value};
+use rustc_serialize::json::{Json, Object};
+
+use graph::{Graph, Node, Edge};
+use graph_builder::{RoadNetwork, Error};
+use label_setting::{LabelProblem, Labels, Stop, label_setting};
+use path::Path;
+use search::{WeightedData, SearchError};
+use spatial_index::SegmentIndex;
+use weight::Weight;
+use wgs84::{WGS84, haversine};
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+
+// All energies are in joules, charging power is in kW.
+
+const GRAVITY : f64 = 9.81;
+const AIR_DENSITY : f64 = 1.2;
+
+// Physical parameters of a vehicle to estimate its energy consumption
+pub struct VehicleModel {
+    // in kg
+    pub mass: f64,
+    pub rolling_resistance: f64,
+    // drag coefficient times frontal area in m²
+    pub drag_area: f64,
+    // share of the battery energy that arrives at the wheels
+    pub drivetrain_efficiency: f64,
+    // share of the braking energy that is recovered into the battery
+    pub regeneration_efficiency: f64,
+    // in W, drawn independent of driving, e.g. by heating
+    pub auxiliary_power: f64
+}
+
+impl Default for VehicleModel {
+    fn default() -> VehicleModel {
+        VehicleModel {
+            mass: 1600.0,
+            rolling_resistance: 0.01,
+            drag_area: 0.65,
+            drivetrain_efficiency: 0.9,
+            regeneration_efficiency: 0.6,
+            auxiliary_power: 500.0
+        }
+    }
+}
+
+impl VehicleModel {
+    // battery energy to drive length meters in duration seconds while climbing height meters,
+    // negative if more is regenerated than used
+    pub fn energy(&self, length: f64, duration: f64, height: f64) -> f64 {
+        let speed = if duration > 0.0 { length / duration } else { 0.0 };
+        let resistance = self.mass * GRAVITY * self.rolling_resistance + 0.5 * AIR_DENSITY * self.drag_area * speed * speed;
+        let traction = resistance * length + self.mass * GRAVITY * height;
+        let battery = if traction >= 0.0 {
+            traction / self.drivetrain_efficiency
+        } else {
+            traction * self.regeneration_efficiency
+        };
+        battery + self.auxiliary_power * duration
+    }
+}
+
+// Energy used on every edge, at the speed given by its travel time. Networks loaded without
+// elevation data are flat.
+pub fn edge_energy(network: &RoadNetwork, model: &VehicleModel) -> Vec<i32> {
+    let graph = &network.graph;
+    let mut energy = Vec::with_capacity(graph.num_edges());
+    for node in graph.nodes() {
+        for edge in graph.edges(node) {
+            let data = graph.data(edge);
+            let height = data.ascent() - data.descent();
+            let duration = WeightedData::<u32>::weight(data) as f64 / 10.0;
+            energy.push(model.energy(data.length(), duration, height).round() as i32);
+        }
+    }
+    energy
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChargingStation {
+    pub node: Node,
+    pub power: f64
+}
+
+fn station_power(map: &Object) -> Option<f64> {
+    match map.get("power") {
+        Some(&Json::String(ref value)) => value.trim().parse().ok(),
+        Some(value) => value.as_f64(),
+        None => None
+    }
+}
+
+// Reads points tagged amenity=charging_station, one GeoJSON feature per line like the road
+// network. Every station is snapped to the closest node of the edges index finds around it,
+// stations further than max_distance meters from every node are skipped. Stations without a
+// numeric power property get default_power.
+pub fn read_charging_stations(path: &String, network: &RoadNetwork, index: &SegmentIndex, default_power: f64, max_distance: f64) -> Result<Vec<ChargingStation>, Error> {
+    let mut reader = BufReader::new(try!(File::open(path)));
+    let mut stations = Vec::new();
+
+    let mut data = String::new();
+    while try!(reader.read_line(&mut data)) > 0 {
+        let feature = match try!(data.parse::<GeoJson>()) {
+            GeoJson::Feature(feature) => feature,
+            _ => return Err(Error::NoFeature)
+        };
+        data.clear();
+
+        let power = match feature.properties {
+            Some(ref map) if map.get("amenity").and_then(|amenity| amenity.as_string()) == Some("charging_station") => {
+                station_power(map).unwrap_or(default_power)
+            },
+            _ => continue
+        };
+        let position = match feature.geometry {
+            Some(ref geometry) => match geometry.value {
+                Value::Point(ref point) if point.len() >= 2 => WGS84 {lon: point[0], lat: point[1]},
+                _ => continue
+            },
+            None => continue
+        };
+
+        // a node within max_distance lies on one of the edges within max_distance
+        let closest = index.nearest_edges(&position, max_distance).iter()
+            .flat_map(|candidate| vec![candidate.source, network.graph.target(candidate.edge)])
+            .map(|node| (haversine(&position, &network.coordinates[node as usize]), node))
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        if let Some((distance, node)) = closest {
+            if distance <= max_distance {
+                stations.push(ChargingStation {node: node, power: power});
+            }
+        }
+    }
+
+    Ok(stations)
+}
+
+pub struct EvOptions {
+    pub capacity: u32,
+    // a stop charges up to one of this many evenly spaced levels of the capacity
+    pub charging_levels: u32
+}
+
+impl Default for EvOptions {
+    fn default() -> EvOptions {
+        // 40 kWh
+        EvOptions {capacity: 144000000, charging_levels: 4}
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChargingStop {
+    pub node: Node,
+    pub energy: u32,
+    // in the unit of the edge weights, deciseconds for road networks
+    pub duration: u32
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvRoute {
+    // the weight includes the time spent charging
+    pub path: Path<u32>,
+    pub stops: Vec<ChargingStop>,
+    // state of charge at the target
+    pub charge: u32
+}
+
+// time in deciseconds to charge energy joules with power kW
+fn charging_duration(energy: u32, power: f64) -> u32 {
+    (energy as f64 / (100.0 * power)).ceil() as u32
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Drive(Edge),
+    Charge
+}
+
+// time and charge, ordered by time and then by more charge
+type Label = (u32, Reverse<u32>);
+
+struct Battery<'a, D: 'a, G: 'a> {
+    graph: &'a G,
+    energy: &'a [i32],
+    capacity: u32,
+    // highest charging power of every node with a station
+    power: HashMap<Node, f64>,
+    levels: Vec<u32>,
+    data: PhantomData<D>
+}
+
+impl<'a, D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>> LabelProblem for Battery<'a, D, G> {
+    type Label = Label;
+    type Step = Step;
+
+    // earlier with at least as much charge
+    fn dominates(&self, a: &Label, b: &Label) -> bool {
+        a.0 <= b.0 && (a.1).0 >= (b.1).0
+    }
+
+    fn extend(&mut self, node: Node, &(weight, Reverse(charge)): &Label, successors: &mut Vec<(Node, Label, Step)>) -> Result<(), SearchError> {
+        if let Some(&power) = self.power.get(&node) {
+            for &level in self.levels.iter().filter(|&&level| level > charge) {
+                if let Some(new_weight) = Weight::checked_add(weight, charging_duration(level - charge, power)) {
+                    successors.push((node, (new_weight, Reverse(level)), Step::Charge));
+                }
+            }
+        }
+        for edge in self.graph.edges(node) {
+            let edge_weight = self.graph.data(edge).weight();
+            if edge_weight == u32::infinity() {
+                continue;
+            }
+            let new_weight = match Weight::checked_add(weight, edge_weight) {
+                Some(new_weight) => new_weight,
+                None => return Err(SearchError::Overflow(node, edge))
+            };
+            let consumption = self.energy[edge as usize];
+            let new_charge = if consumption >= 0 {
+                match charge.checked_sub(consumption as u32) {
+                    Some(new_charge) => new_charge,
+                    None => continue
+                }
+            } else {
+                charge.saturating_add((-consumption) as u32).min(self.capacity)
+            };
+            successors.push((self.graph.target(edge), (new_weight, Reverse(new_charge)), Step::Drive(edge)));
+        }
+        Ok(())
+    }
+}
+
+// Fastest route from source to target that never runs out of battery, starting with the given
+// charge. Every node keeps the labels of (time, charge) that no other label of it dominates,
+// i.e. is earlier and has at least as much charge. Regeneration can not charge beyond the
+// capacity. At a charging station a label can charge up to any of the charging levels, with
+// constant power and the edge weights in deciseconds. energy holds the consumption of every edge.
+pub fn ev_route<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, energy: &[i32], stations: &[ChargingStation], options: &EvOptions, source: Node, target: Node, charge: u32) -> Result<Option<EvRoute>, SearchError> {
+    assert_eq!(energy.len(), graph.num_edges(), "Energy of {} edges for a graph with {} edges", energy.len(), graph.num_edges());
+    let mut power : HashMap<Node, f64> = HashMap::new();
+    for station in stations.iter().filter(|station| station.power > 0.0) {
+        let entry = power.entry(station.node).or_insert(station.power);
+        *entry = entry.max(station.power);
+    }
+    let levels : Vec<u32> = (1..options.charging_levels + 1)
+        .map(|level| (options.capacity as u64 * level as u64 / options.charging_levels as u64) as u32)
+        .collect();
+
+    let mut battery = Battery {graph: graph, energy: energy, capacity: options.capacity, power: power, levels: levels, data: PhantomData};
+    let initial = (0, Reverse(charge.min(options.capacity)));
+    let labels = try!(label_setting(&mut battery, graph.num_nodes(), source, target, initial, Stop::First));
+    Ok(labels.target_label().map(|label| route(&labels, label)))
+}
+
+fn route(labels: &Labels<Label, Step>, label: usize) -> EvRoute {
+    let mut nodes = vec![labels.node(label)];
+    let mut edges = Vec::new();
+    let mut stops = Vec::new();
+    let mut current = label;
+    while let Some((parent, step)) = labels.parent(current) {
+        let (weight, Reverse(charge)) = *labels.label(current);
+        let (parent_weight, Reverse(parent_charge)) = *labels.label(parent);
+        match step {
+            Step::Drive(edge) => {
+                nodes.push(labels.node(parent));
+                edges.push(edge);
+            },
+            Step::Charge => stops.push(ChargingStop {
+                node: labels.node(current),
+                energy: charge - parent_charge,
+                duration: weight - parent_weight
+            })
+        }
+        current = parent;
+    }
+    nodes.reverse();
+    edges.reverse();
+    stops.reverse();
+    let (weight, Reverse(charge)) = *labels.label(label);
+    EvRoute {path: Path {weight: weight, nodes: nodes, edges: edges}, stops: stops, charge: charge}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elevation::{Elevation, ElevationGrid};
+    use graph::*;
+    use graph_builder::*;
+    use test_util::TestData;
+
+    // edges with weight and energy, sorted like the AdjArrayGraph sorts them
+    fn graph(edges: &[(Node, Node, u32, i32)]) -> (AdjArrayGraph<TestData>, Vec<i32>) {
+        let mut edges = edges.to_vec();
+        edges.sort_by_key(|&(source, target, weight, _)| (source, target, weight));
+        let energy = edges.iter().map(|&(_, _, _, energy)| energy).collect();
+        (AdjArrayGraph::new(edges.iter().map(|&(source, target, weight, _)| (source, target, TestData {weight: weight})).collect()), energy)
+    }
+
+    fn options() -> EvOptions {
+        EvOptions {capacity: 10000, charging_levels: 4}
+    }
+
+    #[test]
+    fn consumption() {
+        let model = VehicleModel::default();
+        let flat = model.energy(1000.0, 72.0, 0.0);
+        assert!(flat > 0.0);
+        // air drag grows with speed
+        assert!(model.energy(1000.0, 36.0, 0.0) > flat);
+        assert!(model.energy(1000.0, 72.0, 20.0) > flat);
+        // a steep descent charges the battery, but recovers less than the climb used
+        let descent = model.energy(1000.0, 72.0, -100.0);
+        assert!(descent < 0.0);
+        assert!(-descent < model.energy(1000.0, 72.0, 100.0) - 2.0 * flat);
+    }
+
+    #[test]
+    fn sample_energy() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let model = VehicleModel::default();
+        let flat = edge_energy(&network, &model);
+        assert_eq!(flat.len(), network.graph.num_edges());
+        assert!(flat.iter().all(|&energy| energy > 0));
+
+        // the sample way leads north and descends by 500 m per 0.01 degrees
+        let mut elevation = Elevation::new();
+        elevation.push(ElevationGrid::new(43.74, 7.42, 0.01, 2, 2, vec![0.0, 0.0, 500.0, 500.0]));
+        let options = LoadOptions {elevation: Some(elevation), ..LoadOptions::default()};
+        let network = load_geojson(&String::from("data/sample.geojson"), &options).unwrap();
+        let downhill = edge_energy(&network, &model);
+        assert!(flat.iter().zip(&downhill).all(|(flat, downhill)| downhill < flat));
+    }
+
+    #[test]
+    fn charging_stop() {
+        // 0 -> 1 -> 2 uses more than a full battery, 1 has a 1 kW charger
+        let (g, energy) = graph(&[(0, 1, 10, 6000), (1, 2, 10, 6000)]);
+        let stations = [ChargingStation {node: 1, power: 1.0}];
+        assert_eq!(ev_route(&g, &energy, &[], &options(), 0, 2, 10000), Ok(None));
+
+        // charging to 7500 of 10000 is enough and faster than a full charge
+        let route = ev_route(&g, &energy, &stations, &options(), 0, 2, 10000).unwrap().unwrap();
+        assert_eq!(route.path, Path {weight: 55, nodes: vec![0, 1, 2], edges: vec![0, 1]});
+        assert_eq!(route.stops, vec![ChargingStop {node: 1, energy: 3500, duration: 35}]);
+        assert_eq!(route.charge, 1500);
+
+        // not even the first edge can be driven
+        assert_eq!(ev_route(&g, &energy, &stations, &options(), 0, 2, 5000), Ok(None));
+    }
+
+    #[test]
+    fn detour_without_charging() {
+        // the direct route 0 -> 1 -> 3 needs a charging stop, the detour over 2 does not
+        let (g, energy) = graph(&[(0, 1, 10, 6000), (1, 3, 10, 6000), (0, 2, 30, 4000), (2, 3, 30, 4000)]);
+        let slow = [ChargingStation {node: 1, power: 0.5}];
+        let route = ev_route(&g, &energy, &slow, &options(), 0, 3, 10000).unwrap().unwrap();
+        assert_eq!(route.path.nodes, vec![0, 2, 3]);
+        assert!(route.stops.is_empty());
+
+        let fast = [ChargingStation {node: 1, power: 10.0}];
+        let route = ev_route(&g, &energy, &fast, &options(), 0, 3, 10000).unwrap().unwrap();
+        assert_eq!(route.path.nodes, vec![0, 1, 3]);
+        assert_eq!(route.path.weight, 20 + 4);
+    }
+
+    #[test]
+    fn regeneration() {
+        // 0 -> 3 over 1 descends and recovers energy, but never beyond the capacity
+        let (g, energy) = graph(&[(0, 1, 10, -3000), (1, 2, 10, -3000), (2, 3, 10, 9000), (0, 3, 5, 12000)]);
+        let route = ev_route(&g, &energy, &[], &options(), 0, 3, 3000).unwrap().unwrap();
+        assert_eq!(route.path.nodes, vec![0, 1, 2, 3]);
+        assert_eq!(route.charge, 0);
+        assert_eq!(ev_route(&g, &energy, &[], &options(), 0, 3, 10000).unwrap().unwrap().charge, 1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_energy() {
+        let (g, _) = graph(&[(0, 1, 10, 100)]);
+        let _ = ev_route(&g, &[], &[], &options(), 0, 1, 1000);
+    }
+
+    #[test]
+    fn read_stations() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let index = SegmentIndex::new(&network, 0.001);
+        let stations = read_charging_stations(&String::from("data/charging_stations.geojson"), &network, &index, 11.0, 50.0).unwrap();
+        assert_eq!(stations, vec![
+            ChargingStation {node: network.id_map[&5], power: 50.0},
+            ChargingStation {node: network.id_map[&9], power: 22.0},
+            ChargingStation {node: network.id_map[&1], power: 11.0}
+        ]);
+    }
+}
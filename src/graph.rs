@@ -91,13 +91,12 @@ impl<T: Ord> AdjArrayGraph<T> {
         if let Some(first_start_node) = first_start {
             // now construct the prefix array
             let mut offset : usize = 0;
-            let mut last_start : Node = first_start_node;
             let mut max_node_id : Node = first_start_node;
 
             for (start, target, d) in input_edges.drain(0..) {
-                if start != last_start {
+                // nodes without outgoing edges get an empty range
+                while offsets.len() <= start as usize {
                     offsets.push(offset as u32);
-                    last_start = start;
                 }
                 targets.push(target);
                 data.push(d);
@@ -117,6 +116,35 @@ impl<T: Ord> AdjArrayGraph<T> {
     }
 }
 
+impl<T: Ord + Clone> AdjArrayGraph<T> {
+    // same graph with every edge pointing in the opposite direction,
+    // used for searches towards a target
+    pub fn reversed(&self) -> AdjArrayGraph<T> {
+        let mut edges = Vec::with_capacity(self.num_edges());
+        for source in self.nodes() {
+            for edge in self.edges(source) {
+                edges.push((self.target(edge), source, self.data(edge).clone()));
+            }
+        }
+        AdjArrayGraph::new(edges)
+    }
+}
+
+impl<T> AdjArrayGraph<T> {
+    // returns all edges as (source, target, data) in the order they are stored
+    pub fn into_edges(self) -> Vec<(Node, Node, T)> {
+        let mut edges = Vec::with_capacity(self.targets.len());
+        let mut source : Node = 0;
+        for (idx, (target, d)) in self.targets.into_iter().zip(self.data.into_iter()).enumerate() {
+            while self.offsets[source as usize + 1] as usize <= idx {
+                source += 1;
+            }
+            edges.push((source, target, d));
+        }
+        edges
+    }
+}
+
 impl<T> Graph<T> for AdjArrayGraph<T> {
     type N = Node;
     type E = Edge;
@@ -186,4 +214,20 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn into_edges() {
+        let g: AdjArrayGraph<u32> = AdjArrayGraph::new(vec![
+        (2, 0, 3), (0, 2, 1), (0, 1, 0)
+        ]);
+        assert_eq!(g.into_edges(), vec![(0, 1, 0), (0, 2, 1), (2, 0, 3)]);
+    }
+
+    #[test]
+    fn reversed() {
+        let g : AdjArrayGraph<u32> = AdjArrayGraph::new(vec![(0, 1, 5), (0, 2, 6), (1, 2, 7), (3, 0, 8)]);
+        let r = g.reversed();
+        assert_eq!(r.num_nodes(), 4);
+        assert_eq!(r.into_edges(), vec![(0, 3, 8), (1, 0, 5), (2, 0, 6), (2, 1, 7)]);
+    }
 }
@@ -3,9 +3,11 @@ use geojson::{Feature, Value, GeoJson};
 
 use rustc_serialize::json::Json;
 
-use graph::{Node, AdjArrayGraph};
+use graph::{Node, Edge, Graph, AdjArrayGraph};
 use wgs84::{WGS84, haversine};
 use search::{WeightedData};
+use elevation::{Elevation, ElevationProfile, ascent_descent};
+use weight::FloatWeight;
 
 use std::fs::File;
 use std::io;
@@ -13,11 +15,96 @@ use std::io::BufRead;
 use std::io::BufReader;
 use std::collections::BTreeMap;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct EdgeData {
     forward: bool,
     backward: bool,
-    weight: u32
+    weight: u32,
+    // length in decimeters
+    length: u32,
+    // total ascent and descent along the edge in decimeters
+    ascent: u32,
+    descent: u32,
+    // index into the way table of the RoadNetwork
+    way: u32,
+    // index into the EdgeGeometry of the RoadNetwork
+    geometry: u32
+}
+
+impl EdgeData {
+    pub fn forward(&self) -> bool {
+        self.forward
+    }
+
+    pub fn backward(&self) -> bool {
+        self.backward
+    }
+
+    // geometric length in meters
+    pub fn length(&self) -> f64 {
+        self.length as f64 / 10.0
+    }
+
+    // in meters, zero unless the network was loaded with elevation data
+    pub fn ascent(&self) -> f64 {
+        self.ascent as f64 / 10.0
+    }
+
+    pub fn descent(&self) -> f64 {
+        self.descent as f64 / 10.0
+    }
+
+    pub fn way(&self) -> u32 {
+        self.way
+    }
+
+    pub fn geometry(&self) -> u32 {
+        self.geometry
+    }
+
+    // edges can only be merged if they only differ in weight, length and geometry
+    fn is_compatible(&self, other: &EdgeData) -> bool {
+        self.forward == other.forward && self.backward == other.backward && self.way == other.way
+    }
+}
+
+// Stores the shape points between the source and target node of every edge.
+// Uncompressed edges all reference the empty entry 0.
+pub struct EdgeGeometry {
+    offsets: Vec<u32>,
+    coordinates: Vec<WGS84>
+}
+
+impl EdgeGeometry {
+    pub fn new() -> EdgeGeometry {
+        EdgeGeometry {offsets: vec![0, 0], coordinates: Vec::new()}
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn push(&mut self, coordinates: &[WGS84]) -> u32 {
+        let id = self.len() as u32;
+        self.coordinates.extend_from_slice(coordinates);
+        self.offsets.push(self.coordinates.len() as u32);
+        id
+    }
+
+    // intermediate coordinates of the geometry with the given id
+    pub fn get(&self, id: u32) -> &[WGS84] {
+        let idx = id as usize;
+        &self.coordinates[self.offsets[idx] as usize..self.offsets[idx + 1] as usize]
+    }
+}
+
+// Attributes of the OSM way an edge was created from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WayData {
+    pub osm_id: i64,
+    pub name: Option<String>,
+    pub reference: Option<String>,
+    pub highway: String
 }
 
 impl WeightedData<u32> for EdgeData {
@@ -26,6 +113,47 @@ impl WeightedData<u32> for EdgeData {
     }
 }
 
+// travel time, ties are broken by the shorter length
+impl WeightedData<(u32, u32)> for EdgeData {
+    fn weight(&self) -> (u32, u32) {
+        (self.weight, self.length)
+    }
+}
+
+// length in meters
+impl WeightedData<FloatWeight> for EdgeData {
+    fn weight(&self) -> FloatWeight {
+        FloatWeight::new(self.length())
+    }
+}
+
+fn get_string(feature: &Feature, key: &str) -> Option<String> {
+    match feature.properties {
+        None => None,
+        Some(ref map) => match map.get(key) {
+            Some(&Json::String(ref value)) => Some(value.clone()),
+            _ => None
+        }
+    }
+}
+
+// osm_id of ways whose feature has no numeric @id
+pub const UNKNOWN_OSM_ID : i64 = -1;
+
+fn get_way_data(feature: &Feature) -> WayData {
+    let osm_id = match feature.properties {
+        Some(ref map) => map.get("@id").and_then(|id| id.as_i64()).unwrap_or(UNKNOWN_OSM_ID),
+        None => UNKNOWN_OSM_ID
+    };
+
+    WayData {
+        osm_id: osm_id,
+        name: get_string(feature, "name"),
+        reference: get_string(feature, "ref"),
+        highway: get_string(feature, "highway").unwrap_or(String::new())
+    }
+}
+
 fn is_road(feature: &Feature) -> bool {
     match feature.properties {
         None => false,
@@ -100,9 +228,17 @@ fn compute_highway_speed(feature: &Feature) -> f64 {
     }
 }
 
+// weight of an edge with the length in meters traversed at the speed in km/h
+pub fn duration(distance: f64, speed: f64) -> u32 {
+    (distance / speed * 10.0).round() as u32
+}
+
 type OSMEdge = (i64, i64, EdgeData);
-fn roads_to_edges(features: Vec<Feature>) -> Vec<OSMEdge> {
+type CoordinateMap = BTreeMap<i64, WGS84>;
+fn roads_to_edges(features: Vec<Feature>) -> (Vec<OSMEdge>, Vec<WayData>, CoordinateMap) {
     let mut edges = Vec::new();
+    let mut ways = Vec::new();
+    let mut coordinates = CoordinateMap::new();
 
     for feature in features {
         let speed = compute_highway_speed(&feature);
@@ -138,23 +274,104 @@ fn roads_to_edges(features: Vec<Feature>) -> Vec<OSMEdge> {
 
         assert!(nodes.len() == line_string.len());
 
+        let way = ways.len() as u32;
+        ways.push(get_way_data(&feature));
+
+        for (node, coordinate) in nodes.iter().zip(line_string.iter()) {
+            coordinates.insert(node.as_i64().unwrap(), WGS84 {lon: coordinate[0], lat: coordinate[1]});
+        }
+
         for index in 0..nodes.len()-1 {
             let ref prev_node = nodes[index];
             let ref prev_coordinate = line_string[index];
             let ref current_node = nodes[index+1];
             let ref current_coordinate = line_string[index+1];
             let distance = haversine(&WGS84 {lon: prev_coordinate[0], lat: prev_coordinate[1]}, &WGS84 {lon: current_coordinate[0], lat: current_coordinate[1]});
-            let duration = (distance / speed * 10.0).round() as u32;
-            edges.push((prev_node.as_i64().unwrap(), current_node.as_i64().unwrap(), EdgeData {forward: accessibility.0, backward: accessibility.1, weight: duration}));
+            let duration = duration(distance, speed);
+            let length = (distance * 10.0).round() as u32;
+            edges.push((prev_node.as_i64().unwrap(), current_node.as_i64().unwrap(), EdgeData {forward: accessibility.0, backward: accessibility.1, weight: duration, length: length, ascent: 0, descent: 0, way: way, geometry: 0}));
+        }
+    }
+
+    (edges, ways, coordinates)
+}
+
+// Merges chains of degree-2 nodes into a single edge. A node is only removed
+// if it has exactly one incoming and one outgoing edge and both edges are compatible.
+// The coordinates of removed nodes are saved in the geometry of the merged edge.
+fn compress_edges(mut osm_edges: Vec<OSMEdge>, coordinates: &CoordinateMap, geometry: &mut EdgeGeometry) -> Vec<OSMEdge> {
+    let mut out_edges : BTreeMap<i64, Vec<usize>> = BTreeMap::new();
+    let mut in_edges : BTreeMap<i64, Vec<usize>> = BTreeMap::new();
+    for (idx, e) in osm_edges.iter().enumerate() {
+        out_edges.entry(e.0).or_insert(Vec::new()).push(idx);
+        in_edges.entry(e.1).or_insert(Vec::new()).push(idx);
+    }
+
+    let is_removable = |node: &i64| -> bool {
+        match (in_edges.get(node), out_edges.get(node)) {
+            (Some(ref incoming), Some(ref outgoing)) if incoming.len() == 1 && outgoing.len() == 1 => {
+                let ref in_edge = osm_edges[incoming[0]];
+                let ref out_edge = osm_edges[outgoing[0]];
+                // don't turn a -> b -> a into a self-loop
+                in_edge.0 != out_edge.1 && in_edge.2.is_compatible(&out_edge.2)
+            },
+            _ => false
+        }
+    };
+
+    let mut consumed = vec![false; osm_edges.len()];
+    let mut chains : Vec<Vec<usize>> = Vec::new();
+    for start in 0..osm_edges.len() {
+        if is_removable(&osm_edges[start].0) {
+            continue;
+        }
+
+        let mut chain = vec![start];
+        let mut current = start;
+        while is_removable(&osm_edges[current].1) {
+            current = out_edges[&osm_edges[current].1][0];
+            chain.push(current);
+        }
+        for &idx in &chain {
+            consumed[idx] = true;
+        }
+        chains.push(chain);
+    }
+
+    // cycles that only consist of removable nodes are kept as they are
+    for idx in 0..osm_edges.len() {
+        if !consumed[idx] {
+            chains.push(vec![idx]);
         }
     }
 
-    edges
+    let mut taken : Vec<Option<OSMEdge>> = osm_edges.drain(0..).map(|e| Some(e)).collect();
+    let mut compressed = Vec::with_capacity(chains.len());
+    for chain in chains {
+        let (source, mut target, mut data) = taken[chain[0]].take().unwrap();
+        let mut shape = Vec::new();
+        for &idx in &chain[1..] {
+            let (_, next_target, next_data) = taken[idx].take().unwrap();
+            shape.push(coordinates[&target]);
+            // a closed part keeps the whole chain closed
+            data.weight = data.weight.saturating_add(next_data.weight);
+            data.length = data.length.saturating_add(next_data.length);
+            data.ascent = data.ascent.saturating_add(next_data.ascent);
+            data.descent = data.descent.saturating_add(next_data.descent);
+            target = next_target;
+        }
+        if !shape.is_empty() {
+            data.geometry = geometry.push(&shape);
+        }
+        compressed.push((source, target, data));
+    }
+
+    compressed
 }
 
 pub type IdMap = BTreeMap<i64, Node>;
 type InputEdge = (Node, Node, EdgeData);
-// TODO this could be a BFS ordering
+// ids are assigned in input order, use RoadNetwork::renumber for a cache-friendly order
 fn renumber_edges(mut osm_edges : Vec<OSMEdge>) -> (Vec<InputEdge>, IdMap) {
     let mut id_map = IdMap::new();
     for ref e in &osm_edges {
@@ -191,7 +408,78 @@ impl From<geojson::Error> for Error {
     }
 }
 
+// Routing graph together with everything needed to map it back to OSM
+pub struct RoadNetwork {
+    pub graph: AdjArrayGraph<EdgeData>,
+    pub id_map: IdMap,
+    pub ways: Vec<WayData>,
+    pub coordinates: Vec<WGS84>,
+    pub geometry: EdgeGeometry
+}
+
+impl RoadNetwork {
+    pub fn way(&self, edge: Edge) -> &WayData {
+        &self.ways[self.graph.data(edge).way as usize]
+    }
+
+    // full shape of the edge including the coordinates of source and target
+    pub fn edge_geometry(&self, source: Node, edge: Edge) -> Vec<WGS84> {
+        let mut line = vec![self.coordinates[source as usize]];
+        line.extend_from_slice(self.geometry.get(self.graph.data(edge).geometry));
+        line.push(self.coordinates[self.graph.target(edge) as usize]);
+        line
+    }
+
+    // applies a permutation mapping old to new node ids, e.g. computed by a Renumbering
+    pub fn renumber(self, permutation: &[Node]) -> RoadNetwork {
+        assert_eq!(permutation.len(), self.graph.num_nodes());
+
+        let edges = self.graph.into_edges().into_iter().map(|(source, target, data)| {
+            (permutation[source as usize], permutation[target as usize], data)
+        }).collect();
+
+        let mut coordinates = self.coordinates.clone();
+        for (old_id, &new_id) in permutation.iter().enumerate() {
+            coordinates[new_id as usize] = self.coordinates[old_id];
+        }
+
+        let id_map = self.id_map.into_iter().map(|(osm_id, id)| (osm_id, permutation[id as usize])).collect();
+
+        RoadNetwork {graph: AdjArrayGraph::new(edges), id_map: id_map, ways: self.ways, coordinates: coordinates, geometry: self.geometry}
+    }
+
+    // shape of a path given as a sequence of edges starting at source
+    pub fn path_geometry(&self, source: Node, path: &[Edge]) -> Vec<WGS84> {
+        let mut line = vec![self.coordinates[source as usize]];
+        for &edge in path {
+            line.extend_from_slice(self.geometry.get(self.graph.data(edge).geometry));
+            line.push(self.coordinates[self.graph.target(edge) as usize]);
+        }
+        line
+    }
+}
+
+pub struct LoadOptions {
+    // merge chains of degree-2 nodes into single edges
+    pub compress: bool,
+    // used to compute the ascent and descent of every edge
+    pub elevation: Option<Elevation>,
+    // adjusts the travel times by ascent and descent in the direction of the edge
+    pub profile: ElevationProfile
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {compress: false, elevation: None, profile: ElevationProfile::default()}
+    }
+}
+
 pub fn from_geojson(path: &String) -> Result<(AdjArrayGraph<EdgeData>, IdMap), Error> {
+    let network = try!(load_geojson(path, &LoadOptions::default()));
+    Ok((network.graph, network.id_map))
+}
+
+pub fn load_geojson(path: &String, options: &LoadOptions) -> Result<RoadNetwork, Error> {
     let mut features : Vec<Feature> = Vec::new();
     let mut reader = BufReader::new(try!(File::open(path)));
 
@@ -211,15 +499,37 @@ pub fn from_geojson(path: &String) -> Result<(AdjArrayGraph<EdgeData>, IdMap), E
         data.clear();
     }
 
-    let (edges, id_map) = renumber_edges(roads_to_edges(features));
+    let (mut osm_edges, ways, osm_coordinates) = roads_to_edges(features);
+    let mut geometry = EdgeGeometry::new();
+    if options.compress {
+        osm_edges = compress_edges(osm_edges, &osm_coordinates, &mut geometry);
+    }
+    if let Some(ref elevation) = options.elevation {
+        for &mut (source, target, ref mut data) in &mut osm_edges {
+            let mut line = vec![osm_coordinates[&source]];
+            line.extend_from_slice(geometry.get(data.geometry));
+            line.push(osm_coordinates[&target]);
+            let (ascent, descent) = ascent_descent(elevation, &line);
+            data.ascent = (ascent * 10.0).round() as u32;
+            data.descent = (descent * 10.0).round() as u32;
+            data.weight = options.profile.adjust(data.weight, ascent, descent);
+        }
+    }
+    let (edges, id_map) = renumber_edges(osm_edges);
+
+    let mut coordinates = vec![WGS84 {lon: 0.0, lat: 0.0}; id_map.len()];
+    for (osm_id, &id) in &id_map {
+        coordinates[id as usize] = osm_coordinates[osm_id];
+    }
 
-    Ok((AdjArrayGraph::new(edges), id_map))
+    Ok(RoadNetwork {graph: AdjArrayGraph::new(edges), id_map: id_map, ways: ways, coordinates: coordinates, geometry: geometry})
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use elevation::ElevationGrid;
     use graph::Graph;
 
     #[test]
@@ -228,4 +538,136 @@ mod tests {
         assert_eq!(g.num_nodes(), 9);
         assert_eq!(g.num_edges(), 8);
     }
+
+    #[test]
+    fn load_sample_ways() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        assert_eq!(network.ways.len(), 1);
+        assert_eq!(network.ways[0], WayData {osm_id: 2, name: None, reference: None, highway: String::from("primary")});
+
+        let mut length = 0.0;
+        for node in network.graph.nodes() {
+            for edge in network.graph.edges(node) {
+                assert_eq!(network.way(edge).osm_id, 2);
+                assert!(network.graph.data(edge).forward());
+                assert!(!network.graph.data(edge).backward());
+                length += network.graph.data(edge).length();
+            }
+        }
+        // the sample way is roughly 120m long
+        assert!(length > 100.0 && length < 140.0);
+    }
+
+    #[test]
+    fn way_without_id() {
+        let feature = match r#"{"type":"Feature","geometry":{"type":"LineString","coordinates":[[7.0,43.0],[7.001,43.0]]},"properties":{"@nodes":[1,2],"highway":"residential","name":"Rue"}}"#.parse::<GeoJson>().unwrap() {
+            GeoJson::Feature(feature) => feature,
+            _ => unreachable!()
+        };
+        let (edges, ways, _) = roads_to_edges(vec![feature]);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(ways, vec![WayData {osm_id: UNKNOWN_OSM_ID, name: Some(String::from("Rue")), reference: None, highway: String::from("residential")}]);
+    }
+
+    #[test]
+    fn load_sample_compressed() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions {compress: true, ..LoadOptions::default()}).unwrap();
+        assert_eq!(network.graph.num_nodes(), 2);
+        assert_eq!(network.graph.num_edges(), 1);
+
+        let source = *network.id_map.get(&1).unwrap();
+        let target = *network.id_map.get(&9).unwrap();
+        let edge = network.graph.edges(source).start;
+        assert_eq!(network.graph.target(edge), target);
+        assert!((network.graph.data(edge).length() - 121.3).abs() < 0.5);
+
+        let line = network.edge_geometry(source, edge);
+        assert_eq!(line.len(), 9);
+        assert_eq!(line[0], WGS84 {lon: 7.4259518, lat: 43.7389494});
+        assert_eq!(line[4], WGS84 {lon: 7.4257591, lat: 43.7391896});
+        assert_eq!(line[8], WGS84 {lon: 7.4251533, lat: 43.7397158});
+    }
+
+    #[test]
+    fn load_sample_elevation() {
+        let flat = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        // rises by 100 m per 0.01 degrees to the north, the sample way leads north west
+        let elevation = || {
+            let mut elevation = Elevation::new();
+            elevation.push(ElevationGrid::new(43.74, 7.42, 0.01, 2, 2, vec![100.0, 100.0, 0.0, 0.0]));
+            Some(elevation)
+        };
+        let expected = (43.7397158 - 43.7389494) * 10000.0;
+
+        for &compress in &[false, true] {
+            let options = LoadOptions {compress: compress, elevation: elevation(), profile: ElevationProfile::bicycle()};
+            let network = load_geojson(&String::from("data/sample.geojson"), &options).unwrap();
+            let mut ascent = 0.0;
+            for node in network.graph.nodes() {
+                for edge in network.graph.edges(node) {
+                    let data = network.graph.data(edge);
+                    assert_eq!(data.descent(), 0.0);
+                    ascent += data.ascent();
+                }
+            }
+            assert!((ascent - expected).abs() < 0.5);
+        }
+
+        let options = LoadOptions {elevation: elevation(), profile: ElevationProfile::bicycle(), ..LoadOptions::default()};
+        let network = load_geojson(&String::from("data/sample.geojson"), &options).unwrap();
+        for node in network.graph.nodes() {
+            for edge in network.graph.edges(node) {
+                let climbing : u32 = WeightedData::<u32>::weight(network.graph.data(edge));
+                assert!(climbing > WeightedData::<u32>::weight(flat.graph.data(edge)));
+            }
+        }
+    }
+
+    #[test]
+    fn renumber_sample() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let num_nodes = network.graph.num_nodes() as Node;
+        let osm_coordinate = network.coordinates[*network.id_map.get(&4).unwrap() as usize];
+        // reverse the order of all nodes
+        let permutation : Vec<Node> = (0..num_nodes).map(|id| num_nodes - 1 - id).collect();
+        let renumbered = network.renumber(&permutation);
+
+        assert_eq!(renumbered.graph.num_nodes(), 9);
+        assert_eq!(renumbered.graph.num_edges(), 8);
+        let node = *renumbered.id_map.get(&4).unwrap();
+        assert_eq!(renumbered.coordinates[node as usize], osm_coordinate);
+        let edge = renumbered.graph.edges(node).start;
+        assert_eq!(renumbered.graph.target(edge), *renumbered.id_map.get(&5).unwrap());
+    }
+
+    fn test_edge(forward: bool, backward: bool, way: u32) -> EdgeData {
+        EdgeData {forward: forward, backward: backward, weight: 1, length: 10, ascent: 0, descent: 0, way: way, geometry: 0}
+    }
+
+    // 1 -> 2 -> 3 -> 4 -> 5
+    //           ^
+    //           6 -> 7 <- 8
+    #[test]
+    fn compress_junctions() {
+        let edges = vec![
+            (1, 2, test_edge(true, false, 0)), (2, 3, test_edge(true, false, 0)),
+            (3, 4, test_edge(true, false, 0)), (4, 5, test_edge(true, false, 1)),
+            (6, 3, test_edge(true, false, 2)),
+            (6, 7, test_edge(true, true, 3)), (8, 7, test_edge(true, true, 3))
+        ];
+        let mut coordinates = CoordinateMap::new();
+        for node in 1..9 {
+            coordinates.insert(node, WGS84 {lon: node as f64, lat: 0.0});
+        }
+        let mut geometry = EdgeGeometry::new();
+        let mut compressed = compress_edges(edges, &coordinates, &mut geometry);
+        compressed.sort();
+
+        let endpoints : Vec<(i64, i64, u32)> = compressed.iter().map(|e| (e.0, e.1, e.2.weight)).collect();
+        // 2 is merged, 4 has different way, 7 has two incoming edges
+        assert_eq!(endpoints, vec![(1, 3, 2), (3, 4, 1), (4, 5, 1), (6, 3, 1), (6, 7, 1), (8, 7, 1)]);
+        assert_eq!(geometry.len(), 2);
+        assert_eq!(geometry.get(compressed[0].2.geometry), &[WGS84 {lon: 2.0, lat: 0.0}]);
+        assert_eq!(geometry.get(compressed[1].2.geometry).len(), 0);
+    }
 }
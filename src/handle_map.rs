@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+// index of a handle that was never pushed since the last clear
+pub const NOT_INSERTED : u32 = u32::max_value();
+// index of a handle that was popped from the heap
+pub const REMOVED : u32 = u32::max_value() - 1;
+
+// Storage for the index of every handle inside a heap.
+// Handles that were never set map to NOT_INSERTED.
+pub trait HandleMap {
+    fn get(&self, handle: u32) -> u32;
+    fn set(&mut self, handle: u32, index: u32);
+    // resets every handle to NOT_INSERTED
+    fn clear(&mut self);
+}
+
+// One entry per handle, allocated up front. Fastest for searches that
+// touch a large part of the graph.
+pub struct DenseHandleMap {
+    indices: Vec<u32>,
+    // handles set since the last clear
+    inserted: Vec<u32>
+}
+
+impl DenseHandleMap {
+    pub fn new(num_handles: usize) -> DenseHandleMap {
+        DenseHandleMap {indices: vec![NOT_INSERTED; num_handles], inserted: vec![]}
+    }
+}
+
+impl HandleMap for DenseHandleMap {
+    fn get(&self, handle: u32) -> u32 {
+        self.indices[handle as usize]
+    }
+
+    fn set(&mut self, handle: u32, index: u32) {
+        if self.indices[handle as usize] == NOT_INSERTED {
+            self.inserted.push(handle);
+        }
+        self.indices[handle as usize] = index;
+    }
+
+    fn clear(&mut self) {
+        for &handle in &self.inserted {
+            self.indices[handle as usize] = NOT_INSERTED;
+        }
+        self.inserted.clear();
+    }
+}
+
+// Only stores handles that were set, memory is proportional to the search space.
+pub struct HashHandleMap {
+    indices: HashMap<u32, u32>
+}
+
+impl HashHandleMap {
+    pub fn new() -> HashHandleMap {
+        HashHandleMap {indices: HashMap::new()}
+    }
+}
+
+impl HandleMap for HashHandleMap {
+    fn get(&self, handle: u32) -> u32 {
+        *self.indices.get(&handle).unwrap_or(&NOT_INSERTED)
+    }
+
+    fn set(&mut self, handle: u32, index: u32) {
+        self.indices.insert(handle, index);
+    }
+
+    fn clear(&mut self) {
+        self.indices.clear();
+    }
+}
+
+const PAGE_BITS : u32 = 10;
+const PAGE_SIZE : usize = 1 << PAGE_BITS;
+
+// Splits the handles into pages that are allocated on first use.
+// Local searches on graphs with good locality only touch a few pages.
+pub struct PagedHandleMap {
+    // allocated pages are kept for the next search
+    pages: Vec<Option<Box<[u32]>>>,
+    // handles set since the last clear
+    inserted: Vec<u32>
+}
+
+impl PagedHandleMap {
+    pub fn new(num_handles: usize) -> PagedHandleMap {
+        let num_pages = (num_handles + PAGE_SIZE - 1) / PAGE_SIZE;
+        PagedHandleMap {pages: (0..num_pages).map(|_| None).collect(), inserted: vec![]}
+    }
+}
+
+impl HandleMap for PagedHandleMap {
+    fn get(&self, handle: u32) -> u32 {
+        match self.pages[(handle >> PAGE_BITS) as usize] {
+            Some(ref page) => page[handle as usize % PAGE_SIZE],
+            None => NOT_INSERTED
+        }
+    }
+
+    fn set(&mut self, handle: u32, index: u32) {
+        let page_id = (handle >> PAGE_BITS) as usize;
+        if self.pages[page_id].is_none() {
+            self.pages[page_id] = Some(vec![NOT_INSERTED; PAGE_SIZE].into_boxed_slice());
+        }
+        if let Some(ref mut page) = self.pages[page_id] {
+            if page[handle as usize % PAGE_SIZE] == NOT_INSERTED {
+                self.inserted.push(handle);
+            }
+            page[handle as usize % PAGE_SIZE] = index;
+        }
+    }
+
+    fn clear(&mut self) {
+        for &handle in &self.inserted {
+            if let Some(ref mut page) = self.pages[(handle >> PAGE_BITS) as usize] {
+                page[handle as usize % PAGE_SIZE] = NOT_INSERTED;
+            }
+        }
+        self.inserted.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_map<M: HandleMap>(mut map: M) {
+        assert_eq!(map.get(5), NOT_INSERTED);
+        map.set(5, 0);
+        map.set(2000, 1);
+        map.set(5, REMOVED);
+        assert_eq!(map.get(5), REMOVED);
+        assert_eq!(map.get(2000), 1);
+        assert_eq!(map.get(2001), NOT_INSERTED);
+        map.clear();
+        assert_eq!(map.get(5), NOT_INSERTED);
+        assert_eq!(map.get(2000), NOT_INSERTED);
+    }
+
+    #[test]
+    fn dense_map() {
+        check_map(DenseHandleMap::new(3000));
+    }
+
+    #[test]
+    fn hash_map() {
+        check_map(HashHandleMap::new());
+    }
+
+    #[test]
+    fn paged_map() {
+        let map = PagedHandleMap::new(3000);
+        assert_eq!(map.pages.len(), 3);
+        check_map(map);
+    }
+
+    #[test]
+    fn paged_map_clear_only_resets_set_handles() {
+        let mut map = PagedHandleMap::new(3000);
+        map.set(5, 0);
+        map.clear();
+        assert!(map.inserted.is_empty());
+        map.set(6, 1);
+        assert_eq!(map.inserted, vec![6]);
+        map.clear();
+        assert_eq!(map.get(5), NOT_INSERTED);
+        assert_eq!(map.get(6), NOT_INSERTED);
+    }
+}
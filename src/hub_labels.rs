@@ -0,0 +1,252 @@
+use addressable_heap::{AddressableHeap, AddressableBinaryHeap};
+use graph::{Graph, Node, Edge};
+use partition::MultilevelPartition;
+use search::{WeightedData, SearchError};
+use weight::Weight;
+
+// Hub labels built with pruned landmark labeling, see Akiba et al., "Fast Exact
+// Shortest-Path Distance Queries on Large Networks by Pruned Landmark Labeling".
+// Every node stores hubs with the distance to them (forward) and from them (backward),
+// every shortest path from s to t contains a hub in the forward label of s and
+// the backward label of t.
+pub struct HubLabels {
+    forward: Labels,
+    backward: Labels
+}
+
+// Labels of all nodes in one array, the label of node v is at offsets[v]..offsets[v + 1].
+// Hubs are stored by their rank in the order and every label is sorted by rank.
+struct Labels {
+    offsets: Vec<u32>,
+    hubs: Vec<u32>,
+    distances: Vec<u32>
+}
+
+impl Labels {
+    fn new(labels: Vec<Vec<(u32, u32)>>) -> Labels {
+        let mut offsets = Vec::with_capacity(labels.len() + 1);
+        let mut hubs = Vec::new();
+        let mut distances = Vec::new();
+        offsets.push(0);
+        for label in labels {
+            for (hub, distance) in label {
+                hubs.push(hub);
+                distances.push(distance);
+            }
+            offsets.push(hubs.len() as u32);
+        }
+        Labels {offsets: offsets, hubs: hubs, distances: distances}
+    }
+
+    fn label(&self, node: Node) -> (&[u32], &[u32]) {
+        let range = self.offsets[node as usize] as usize..self.offsets[node as usize + 1] as usize;
+        (&self.hubs[range.clone()], &self.distances[range])
+    }
+}
+
+fn degrees<T, G: Graph<T, N=Node, E=Edge>>(graph: &G) -> Vec<u32> {
+    let mut degrees = vec![0; graph.num_nodes()];
+    for node in graph.nodes() {
+        for edge in graph.edges(node) {
+            degrees[node as usize] += 1;
+            degrees[graph.target(edge) as usize] += 1;
+        }
+    }
+    degrees
+}
+
+// Nodes sorted by the number of incident edges, highest first.
+// Nodes with many edges tend to lie on many shortest paths.
+pub fn degree_order<T, G: Graph<T, N=Node, E=Edge>>(graph: &G) -> Vec<Node> {
+    let degrees = degrees(graph);
+    let mut order : Vec<Node> = graph.nodes().collect();
+    order.sort_by(|&a, &b| degrees[b as usize].cmp(&degrees[a as usize]).then(a.cmp(&b)));
+    order
+}
+
+// Nodes sorted by the number of levels they are a boundary node on, ties broken by degree.
+// Boundary nodes of the coarse cells separate the graph like a nested dissection,
+// which gives much smaller labels than the degree order on road networks.
+pub fn partition_order<T, G: Graph<T, N=Node, E=Edge>>(graph: &G, partition: &MultilevelPartition) -> Vec<Node> {
+    let degrees = degrees(graph);
+    let mut boundary_levels = vec![0; graph.num_nodes()];
+    for node in graph.nodes() {
+        for edge in graph.edges(node) {
+            let target = graph.target(edge);
+            // cells are nested, so a boundary node of a level is one on all finer levels as well
+            let levels = (0..partition.num_levels()).filter(|&level| partition.cell(level, node) != partition.cell(level, target)).count();
+            boundary_levels[node as usize] = boundary_levels[node as usize].max(levels);
+            boundary_levels[target as usize] = boundary_levels[target as usize].max(levels);
+        }
+    }
+    let mut order : Vec<Node> = graph.nodes().collect();
+    order.sort_by(|&a, &b| {
+        boundary_levels[b as usize].cmp(&boundary_levels[a as usize])
+            .then(degrees[b as usize].cmp(&degrees[a as usize]))
+            .then(a.cmp(&b))
+    });
+    order
+}
+
+// minimum of the sums over all common hubs of the two labels
+fn merge(first: (&[u32], &[u32]), second: (&[u32], &[u32])) -> u32 {
+    let (first_hubs, first_distances) = first;
+    let (second_hubs, second_distances) = second;
+    let mut best = u32::infinity();
+    let (mut i, mut j) = (0, 0);
+    while i < first_hubs.len() && j < second_hubs.len() {
+        if first_hubs[i] < second_hubs[j] {
+            i += 1;
+        } else if first_hubs[i] > second_hubs[j] {
+            j += 1;
+        } else {
+            best = best.min(first_distances[i].saturating_add(second_distances[j]));
+            i += 1;
+            j += 1;
+        }
+    }
+    best
+}
+
+impl HubLabels {
+    // Builds the labels by running a pruned search from every node in the given order,
+    // the most important nodes have to come first. backward has to be the reversed forward graph.
+    pub fn new<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(forward: &G, backward: &G, order: &[Node]) -> Result<HubLabels, SearchError> {
+        assert_eq!(order.len(), forward.num_nodes());
+        let num_nodes = forward.num_nodes();
+        let mut forward_labels : Vec<Vec<(u32, u32)>> = vec![Vec::new(); num_nodes];
+        let mut backward_labels : Vec<Vec<(u32, u32)>> = vec![Vec::new(); num_nodes];
+        let mut heap = AddressableBinaryHeap::new(num_nodes);
+        // distances of the label of the current hub indexed by rank
+        let mut hub_distances = vec![u32::infinity(); num_nodes];
+
+        for (rank, &hub) in order.iter().enumerate() {
+            let rank = rank as u32;
+            // the forward search adds the hub to backward labels and the other way around
+            try!(pruned_search(forward, &mut heap, &mut hub_distances, hub, rank, &forward_labels, &mut backward_labels));
+            try!(pruned_search(backward, &mut heap, &mut hub_distances, hub, rank, &backward_labels, &mut forward_labels));
+        }
+
+        Ok(HubLabels {forward: Labels::new(forward_labels), backward: Labels::new(backward_labels)})
+    }
+
+    pub fn distance(&self, source: Node, target: Node) -> Option<u32> {
+        let distance = merge(self.forward.label(source), self.backward.label(target));
+        if distance == u32::infinity() { None } else { Some(distance) }
+    }
+
+    // average number of hubs in the forward and backward label of a node
+    pub fn average_label_size(&self) -> f64 {
+        let num_nodes = self.forward.offsets.len() - 1;
+        if num_nodes == 0 {
+            return 0.0;
+        }
+        (self.forward.hubs.len() + self.backward.hubs.len()) as f64 / (2 * num_nodes) as f64
+    }
+}
+
+// Dijkstra from hub that adds the hub to the target labels of every settled node,
+// unless the labels built so far already contain a path that is at least as short.
+// hub_labels are the labels of the hub in search direction.
+fn pruned_search<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G, heap: &mut AddressableBinaryHeap<u32>, hub_distances: &mut [u32], hub: Node, rank: u32, hub_labels: &[Vec<(u32, u32)>], target_labels: &mut [Vec<(u32, u32)>]) -> Result<(), SearchError> {
+    for &(other_rank, distance) in &hub_labels[hub as usize] {
+        hub_distances[other_rank as usize] = distance;
+    }
+
+    heap.clear();
+    heap.push(hub, 0);
+    let mut result = Ok(());
+    'search: while let Some((node, weight)) = heap.pop() {
+        let covered = target_labels[node as usize].iter().any(|&(other_rank, distance)| {
+            hub_distances[other_rank as usize].saturating_add(distance) <= weight
+        });
+        if covered {
+            continue;
+        }
+        target_labels[node as usize].push((rank, weight));
+
+        for edge in graph.edges(node) {
+            let target = graph.target(edge);
+            let edge_weight = graph.data(edge).weight();
+            if edge_weight == u32::infinity() {
+                continue;
+            }
+            let total_weight = match weight.checked_add(edge_weight) {
+                Some(total_weight) => total_weight,
+                None => {
+                    result = Err(SearchError::Overflow(node, edge));
+                    break 'search;
+                }
+            };
+            if heap.in_heap(target) {
+                if total_weight < heap.key(target) {
+                    heap.decrease(target, total_weight);
+                }
+            } else if !heap.was_inserted(target) {
+                heap.push(target, total_weight);
+            }
+        }
+    }
+
+    for &(other_rank, _) in &hub_labels[hub as usize] {
+        hub_distances[other_rank as usize] = u32::infinity();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use search::*;
+    use test_util::{TestData, graph, grid};
+
+    fn check_distances(graph: &AdjArrayGraph<TestData>, labels: &HubLabels) {
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        for source in graph.nodes() {
+            for target in graph.nodes() {
+                assert_eq!(labels.distance(source, target), dijkstra(graph, &mut heap, source, target).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn grid_degree_order() {
+        let graph = grid(9, 7);
+        let labels = HubLabels::new(&graph, &graph.reversed(), &degree_order(&graph)).unwrap();
+        check_distances(&graph, &labels);
+        // pruning keeps the labels much smaller than the number of nodes
+        assert!(labels.average_label_size() < 30.0);
+    }
+
+    #[test]
+    fn grid_partition_order() {
+        let graph = grid(9, 7);
+        let partition = MultilevelPartition::from_bisection(graph.num_nodes(), &[2, 8, 32], |nodes| {
+            nodes.sort();
+            nodes.len() / 2
+        });
+        let order = partition_order(&graph, &partition);
+        let labels = HubLabels::new(&graph, &graph.reversed(), &order).unwrap();
+        check_distances(&graph, &labels);
+        assert!(labels.average_label_size() < HubLabels::new(&graph, &graph.reversed(), &degree_order(&graph)).unwrap().average_label_size());
+    }
+
+    #[test]
+    fn grid_input_order() {
+        let graph = grid(6, 5);
+        let order : Vec<Node> = graph.nodes().collect();
+        let labels = HubLabels::new(&graph, &graph.reversed(), &order).unwrap();
+        check_distances(&graph, &labels);
+    }
+
+    // 0 <-> 1 -> 2    3
+    #[test]
+    fn unreachable() {
+        let graph = graph(&[(0, 1, 1), (1, 0, 2), (1, 2, 3), (3, 3, 1)]);
+        let labels = HubLabels::new(&graph, &graph.reversed(), &degree_order(&graph)).unwrap();
+        check_distances(&graph, &labels);
+        assert_eq!(labels.distance(2, 0), None);
+        assert_eq!(labels.distance(0, 2), Some(4));
+    }
+}
@@ -0,0 +1,243 @@
+use addressable_heap::{AddressableHeap, AddressableBinaryHeap};
+use graph::{Graph, Node, Edge};
+use path::Path;
+use search::{WeightedData, SearchError};
+use weight::Weight;
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+// Nodes and edges a search must not use, only the masked entries are reset afterwards.
+struct Mask {
+    nodes: Vec<bool>,
+    edges: Vec<bool>,
+    masked_nodes: Vec<Node>,
+    masked_edges: Vec<Edge>
+}
+
+impl Mask {
+    fn new(num_nodes: usize, num_edges: usize) -> Mask {
+        Mask {nodes: vec![false; num_nodes], edges: vec![false; num_edges], masked_nodes: Vec::new(), masked_edges: Vec::new()}
+    }
+
+    fn mask_node(&mut self, node: Node) {
+        if !self.nodes[node as usize] {
+            self.nodes[node as usize] = true;
+            self.masked_nodes.push(node);
+        }
+    }
+
+    fn mask_edge(&mut self, edge: Edge) {
+        if !self.edges[edge as usize] {
+            self.edges[edge as usize] = true;
+            self.masked_edges.push(edge);
+        }
+    }
+
+    fn clear(&mut self) {
+        for &node in &self.masked_nodes {
+            self.nodes[node as usize] = false;
+        }
+        for &edge in &self.masked_edges {
+            self.edges[edge as usize] = false;
+        }
+        self.masked_nodes.clear();
+        self.masked_edges.clear();
+    }
+}
+
+// Dijkstra that skips masked nodes and edges and returns the path to target
+fn masked_dijkstra<K: Weight + Debug, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>>(graph: &G, heap: &mut AddressableBinaryHeap<K>, mask: &Mask, source: Node, target: Node) -> Result<Option<Path<K>>, SearchError> {
+    heap.clear();
+    heap.push(source, K::zero());
+    let mut parents : HashMap<Node, (Node, Edge)> = HashMap::new();
+
+    while let Some((node, weight)) = heap.pop() {
+        if node == target {
+            let mut nodes = vec![target];
+            let mut edges = Vec::new();
+            let mut current = target;
+            while current != source {
+                let (parent, edge) = parents[&current];
+                nodes.push(parent);
+                edges.push(edge);
+                current = parent;
+            }
+            nodes.reverse();
+            edges.reverse();
+            return Ok(Some(Path {weight: weight, nodes: nodes, edges: edges}));
+        }
+
+        for edge in graph.edges(node) {
+            let edge_target = graph.target(edge);
+            let edge_weight = graph.data(edge).weight();
+            if mask.edges[edge as usize] || mask.nodes[edge_target as usize] || edge_weight == K::infinity() {
+                continue;
+            }
+            // settled
+            if !heap.in_heap(edge_target) && heap.was_inserted(edge_target) {
+                continue;
+            }
+            let total_weight = match weight.checked_add(edge_weight) {
+                Some(total_weight) => total_weight,
+                None => return Err(SearchError::Overflow(node, edge))
+            };
+            if heap.in_heap(edge_target) {
+                if total_weight < heap.key(edge_target) {
+                    heap.decrease(edge_target, total_weight);
+                    parents.insert(edge_target, (node, edge));
+                }
+            } else {
+                heap.push(edge_target, total_weight);
+                parents.insert(edge_target, (node, edge));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// The k shortest paths from source to target that visit no node twice, sorted by weight,
+// see Yen, "Finding the K Shortest Loopless Paths in a Network".
+// Every next path leaves one of the paths found so far at some spur node. The search from
+// the spur node masks the nodes before it and the edges that previous paths with the same
+// prefix took, so it can neither loop nor repeat a path.
+pub fn k_shortest_paths<K: Weight + Debug, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>>(graph: &G, heap: &mut AddressableBinaryHeap<K>, source: Node, target: Node, k: usize) -> Result<Vec<Path<K>>, SearchError> {
+    let mut mask = Mask::new(graph.num_nodes(), graph.num_edges());
+    let mut paths : Vec<Path<K>> = Vec::new();
+    if k == 0 {
+        return Ok(paths);
+    }
+    match try!(masked_dijkstra(graph, heap, &mask, source, target)) {
+        Some(path) => paths.push(path),
+        None => return Ok(paths)
+    }
+
+    let mut candidates : Vec<Path<K>> = Vec::new();
+    while paths.len() < k {
+        {
+            let last = paths.last().unwrap();
+            let mut root_weight = K::zero();
+            for spur_index in 0..last.edges.len() {
+                let spur_node = last.nodes[spur_index];
+                let ref root_edges = last.edges[..spur_index];
+                for path in &paths {
+                    if path.edges.len() > spur_index && path.edges[..spur_index] == *root_edges {
+                        mask.mask_edge(path.edges[spur_index]);
+                    }
+                }
+                for &node in &last.nodes[..spur_index] {
+                    mask.mask_node(node);
+                }
+
+                let spur = try!(masked_dijkstra(graph, heap, &mask, spur_node, target));
+                mask.clear();
+                if let Some(spur) = spur {
+                    let mut nodes = last.nodes[..spur_index].to_vec();
+                    nodes.extend_from_slice(&spur.nodes);
+                    let mut edges = root_edges.to_vec();
+                    edges.extend_from_slice(&spur.edges);
+                    // the root path and spur path are both valid, only their sum can overflow
+                    let weight = match root_weight.checked_add(spur.weight) {
+                        Some(weight) => weight,
+                        None => return Err(SearchError::Overflow(spur_node, spur.edges[0]))
+                    };
+                    if !candidates.iter().any(|candidate| candidate.edges == edges) {
+                        candidates.push(Path {weight: weight, nodes: nodes, edges: edges});
+                    }
+                }
+
+                // the weight of a prefix of a found path can not overflow
+                root_weight = root_weight.saturating_add(graph.data(last.edges[spur_index]).weight());
+            }
+        }
+
+        // ties are broken by the number of edges, so the result does not depend on the search order
+        let best = candidates.iter().enumerate()
+            .min_by(|&(_, a), &(_, b)| a.weight.cmp(&b.weight).then(a.edges.len().cmp(&b.edges.len())).then(a.edges.cmp(&b.edges)))
+            .map(|(index, _)| index);
+        match best {
+            Some(index) => paths.push(candidates.swap_remove(index)),
+            None => break
+        }
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::graph;
+
+    fn weights_and_nodes(paths: &[Path<u32>]) -> Vec<(u32, Vec<Node>)> {
+        paths.iter().map(|path| (path.weight, path.nodes.clone())).collect()
+    }
+
+    // example from the Wikipedia article on Yen's algorithm with C..H as 0..5
+    //
+    //      1 -4- 3
+    //     /|     |\
+    //    3 |2   1| 2
+    //   /  |     |  \
+    //  0   |  2  4   5
+    //   \  | /   |  /
+    //    2 |/   2| 1
+    //     \2 -3- 5
+    #[test]
+    fn wikipedia_example() {
+        let g = graph(&[(0, 1, 3), (0, 2, 2), (1, 3, 4), (2, 1, 1), (2, 3, 2), (2, 4, 3), (3, 4, 2), (3, 5, 1), (4, 5, 2)]);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        let paths = k_shortest_paths(&g, &mut heap, 0, 5, 3).unwrap();
+        assert_eq!(weights_and_nodes(&paths), vec![(5, vec![0, 2, 3, 5]), (7, vec![0, 2, 4, 5]), (8, vec![0, 1, 3, 5])]);
+        for path in &paths {
+            let weight : u32 = path.edges.iter().map(|&edge| g.data(edge).weight).sum();
+            assert_eq!(weight, path.weight);
+            assert_eq!(path.edges.len() + 1, path.nodes.len());
+        }
+    }
+
+    // all simple paths of a small complete graph in both directions
+    #[test]
+    fn all_paths() {
+        let mut edges = Vec::new();
+        for source in 0..4 {
+            for target in 0..4 {
+                if source != target {
+                    edges.push((source, target, 1 + (source * 3 + target * 5) % 7));
+                }
+            }
+        }
+        let g = graph(&edges);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        // 0 -> 3 directly, over one of 2 nodes or over both in 2 orders
+        let paths = k_shortest_paths(&g, &mut heap, 0, 3, 10).unwrap();
+        assert_eq!(paths.len(), 5);
+        assert!(paths.windows(2).all(|pair| pair[0].weight <= pair[1].weight));
+        for path in &paths {
+            let mut nodes = path.nodes.clone();
+            nodes.sort();
+            nodes.dedup();
+            assert_eq!(nodes.len(), path.nodes.len());
+        }
+    }
+
+    #[test]
+    fn parallel_edges_and_loops() {
+        // 0 => 1 -> 2 with a cycle 1 -> 3 -> 1
+        let g = graph(&[(0, 1, 1), (0, 1, 4), (1, 2, 1), (1, 3, 1), (3, 1, 1)]);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        let paths = k_shortest_paths(&g, &mut heap, 0, 2, 5).unwrap();
+        assert_eq!(weights_and_nodes(&paths), vec![(2, vec![0, 1, 2]), (5, vec![0, 1, 2])]);
+        assert!(paths[0].edges != paths[1].edges);
+    }
+
+    #[test]
+    fn unreachable() {
+        let g = graph(&[(0, 1, 1), (2, 1, 1)]);
+        let mut heap = AddressableBinaryHeap::new(g.num_nodes());
+        assert_eq!(k_shortest_paths(&g, &mut heap, 0, 2, 3), Ok(vec![]));
+        assert_eq!(k_shortest_paths(&g, &mut heap, 0, 1, 0), Ok(vec![]));
+        assert_eq!(k_shortest_paths(&g, &mut heap, 0, 0, 2).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use graph::{Node, Edge};
+use path::Path;
+use search::SearchError;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// Problem specific part of a label setting search: what a label is, when one label makes
+// another one useless and how labels are extended.
+pub trait LabelProblem {
+    // labels are settled in increasing order
+    type Label: Clone + Ord;
+    // how a label was reached from its parent, e.g. the edge
+    type Step: Copy;
+
+    // a is nowhere worse than b, so of equal labels only the first one is kept
+    fn dominates(&self, a: &Self::Label, b: &Self::Label) -> bool;
+    // pushes the labels that follow from label at node by one step
+    fn extend(&mut self, node: Node, label: &Self::Label, successors: &mut Vec<(Node, Self::Label, Self::Step)>) -> Result<(), SearchError>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    // at the first label settled at the target
+    First,
+    // once the labels at the target are Pareto optimal. New labels dominated by a label at
+    // the target are pruned, which requires that no criterion improves along a path.
+    Front
+}
+
+struct Entry<L, S> {
+    label: L,
+    node: Node,
+    // label and step this label was extended from
+    parent: Option<(usize, S)>,
+    dominated: bool
+}
+
+// All labels created by a search, labels are referred to by their id
+pub struct Labels<L, S> {
+    entries: Vec<Entry<L, S>>,
+    // ids of the labels of every node that no other label of it dominates
+    bags: Vec<Vec<usize>>,
+    target: Option<usize>
+}
+
+impl<L, S: Copy> Labels<L, S> {
+    pub fn label(&self, id: usize) -> &L {
+        &self.entries[id].label
+    }
+
+    pub fn node(&self, id: usize) -> Node {
+        self.entries[id].node
+    }
+
+    pub fn parent(&self, id: usize) -> Option<(usize, S)> {
+        self.entries[id].parent
+    }
+
+    pub fn bag(&self, node: Node) -> &[usize] {
+        &self.bags[node as usize]
+    }
+
+    // first label settled at the target
+    pub fn target_label(&self) -> Option<usize> {
+        self.target
+    }
+}
+
+impl<L> Labels<L, Edge> {
+    // the path that leads to the label
+    pub fn path<K>(&self, id: usize, weight: K) -> Path<K> {
+        let mut nodes = vec![self.node(id)];
+        let mut edges = Vec::new();
+        let mut current = id;
+        while let Some((parent, edge)) = self.parent(current) {
+            nodes.push(self.node(parent));
+            edges.push(edge);
+            current = parent;
+        }
+        nodes.reverse();
+        edges.reverse();
+        Path {weight: weight, nodes: nodes, edges: edges}
+    }
+}
+
+// Multi-criteria label setting search from source to target. Every node keeps the labels that
+// no other label of it dominates. Labels are extended in increasing order, so as long as
+// extending never makes a label smaller, a label is never dominated once it was extended.
+pub fn label_setting<P: LabelProblem>(problem: &mut P, num_nodes: usize, source: Node, target: Node, initial: P::Label, stop: Stop) -> Result<Labels<P::Label, P::Step>, SearchError> {
+    let mut labels = Labels {
+        entries: vec![Entry {label: initial.clone(), node: source, parent: None, dominated: false}],
+        bags: vec![Vec::new(); num_nodes],
+        target: None
+    };
+    labels.bags[source as usize].push(0);
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((initial, 0)));
+    let mut successors = Vec::new();
+
+    while let Some(Reverse((label, id))) = queue.pop() {
+        if labels.entries[id].dominated {
+            continue;
+        }
+        let node = labels.entries[id].node;
+        if node == target {
+            if labels.target.is_none() {
+                labels.target = Some(id);
+            }
+            match stop {
+                Stop::First => break,
+                Stop::Front => continue
+            }
+        }
+
+        try!(problem.extend(node, &label, &mut successors));
+        for (head, new_label, step) in successors.drain(..) {
+            let target_bag : &[usize] = if stop == Stop::Front { &labels.bags[target as usize] } else { &[] };
+            if target_bag.iter().chain(&labels.bags[head as usize]).any(|&other| problem.dominates(&labels.entries[other].label, &new_label)) {
+                continue;
+            }
+            let ref mut entries = labels.entries;
+            labels.bags[head as usize].retain(|&other| {
+                let dominated = problem.dominates(&new_label, &entries[other].label);
+                entries[other].dominated |= dominated;
+                !dominated
+            });
+
+            let new_id = entries.len();
+            queue.push(Reverse((new_label.clone(), new_id)));
+            entries.push(Entry {label: new_label, node: head, parent: Some((id, step)), dominated: false});
+            labels.bags[head as usize].push(new_id);
+        }
+    }
+
+    Ok(labels)
+}
@@ -4,6 +4,37 @@ extern crate geojson;
 pub mod graph_builder;
 pub mod graph;
 pub mod addressable_heap;
+pub mod handle_map;
+pub mod dary_heap;
+pub mod pairing_heap;
+pub mod radix_heap;
+pub mod bucket_queue;
+pub mod weight;
 pub mod search;
+pub mod path;
+pub mod search_space;
+pub mod alt;
+pub mod partition;
+pub mod crp;
+pub mod ch;
+pub mod tnr;
+pub mod hub_labels;
+pub mod k_shortest;
+pub mod alternatives;
+pub mod time_dependent;
+pub mod traffic;
+pub mod label_setting;
+pub mod pareto;
+pub mod constrained;
+pub mod ev;
+pub mod elevation;
+pub mod spatial_index;
+pub mod map_matching;
+pub mod renumbering;
+pub mod random;
+pub mod benchmark;
 pub mod wgs84;
 
+
+#[cfg(test)]
+mod test_util;
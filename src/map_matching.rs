@@ -0,0 +1,333 @@
+use addressable_heap::AddressableHeap;
+use graph::{Graph, Node, Edge};
+use graph_builder::RoadNetwork;
+use search::{SearchVisitor, SearchError, dijkstra_bounded};
+use spatial_index::{SegmentIndex, EdgeCandidate};
+use weight::FloatWeight;
+use wgs84::{WGS84, haversine};
+
+use std::collections::HashMap;
+use std::f64;
+use std::io;
+use std::io::{Read, Write};
+
+pub struct MatchingOptions {
+    // edges within this many meters of a GPS point are candidates for it
+    pub radius: f64,
+    pub max_candidates: usize,
+    // standard deviation of the GPS noise in meters
+    pub sigma: f64,
+    // meters by which route and great circle distance between two points may differ
+    // before a transition gets e times less likely
+    pub beta: f64,
+    // routes between the candidates of consecutive points that are longer than this many times
+    // the great circle distance of the points plus twice the radius are not considered
+    pub max_detour: f64
+}
+
+impl Default for MatchingOptions {
+    // values of Newson and Krumm, "Hidden Markov Map Matching Through Noise and Sparseness"
+    fn default() -> MatchingOptions {
+        MatchingOptions {radius: 50.0, max_candidates: 8, sigma: 4.07, beta: 3.0, max_detour: 2.0}
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchedPoint {
+    // position in the trace
+    pub index: usize,
+    pub candidate: EdgeCandidate
+}
+
+// A continuous part of a trace matched to a path through the network, the path covers
+// the whole edges of the first and last matched point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub points: Vec<MatchedPoint>,
+    pub source: Node,
+    pub edges: Vec<Edge>
+}
+
+impl Match {
+    pub fn geometry(&self, network: &RoadNetwork) -> Vec<WGS84> {
+        network.path_geometry(self.source, &self.edges)
+    }
+}
+
+// Shortest path tree of a search from the end of a candidate's edge
+struct Tree {
+    parents: HashMap<Node, (Node, Edge)>,
+    distances: HashMap<Node, f64>
+}
+
+impl SearchVisitor<FloatWeight> for Tree {
+    fn settle(&mut self, node: Node, weight: FloatWeight) {
+        self.distances.insert(node, weight.value());
+    }
+
+    fn push(&mut self, source: Node, edge: Edge, target: Node, _: FloatWeight) {
+        self.parents.insert(target, (source, edge));
+    }
+
+    fn decrease(&mut self, source: Node, edge: Edge, target: Node, _: FloatWeight) {
+        self.parents.insert(target, (source, edge));
+    }
+}
+
+// length in meters of a route between two candidates and the edges it enters on the way
+type Route = Option<(f64, Vec<Edge>)>;
+
+// Length in meters of the shortest route from one candidate to each of the candidates of the next
+// point. A single search settles the sources of all their edges, candidates whose source is
+// more than max_distance away count as unreachable.
+fn routes<H: AddressableHeap<FloatWeight, Handle=Node>>(network: &RoadNetwork, heap: &mut H, tree: &mut Tree, from: &EdgeCandidate, candidates: &[EdgeCandidate], max_distance: f64) -> Result<Vec<Route>, SearchError> {
+    let start = network.graph.target(from.edge);
+    let remaining = (network.graph.data(from.edge).length() - from.offset).max(0.0);
+    tree.parents.clear();
+    tree.distances.clear();
+    try!(dijkstra_bounded(&network.graph, heap, start, FloatWeight::new(max_distance), tree));
+
+    Ok(candidates.iter().map(|to| {
+        if from.edge == to.edge && to.offset >= from.offset {
+            return Some((to.offset - from.offset, Vec::new()));
+        }
+        let distance = match tree.distances.get(&to.source) {
+            Some(&distance) => distance,
+            None => return None
+        };
+
+        let mut edges = vec![to.edge];
+        let mut node = to.source;
+        while node != start {
+            let (parent, edge) = tree.parents[&node];
+            edges.push(edge);
+            node = parent;
+        }
+        edges.reverse();
+        Some((remaining + distance + to.offset, edges))
+    }).collect())
+}
+
+struct Step {
+    index: usize,
+    candidates: Vec<EdgeCandidate>,
+    // log probability of the most likely sequence ending in each candidate
+    scores: Vec<f64>,
+    // candidate of the previous step on that sequence and the edges entered in between
+    previous: Vec<Option<(usize, Vec<Edge>)>>
+}
+
+// follows the most likely sequence of candidates backwards
+fn decode(steps: &[Step]) -> Match {
+    let last = steps.last().unwrap();
+    let mut candidate = (0..last.candidates.len()).fold(0, |best, candidate| if last.scores[candidate] > last.scores[best] { candidate } else { best });
+
+    let mut points = Vec::new();
+    let mut routes = Vec::new();
+    for step in steps.iter().rev() {
+        points.push(MatchedPoint {index: step.index, candidate: step.candidates[candidate]});
+        if let Some((previous, ref edges)) = step.previous[candidate] {
+            routes.push(edges);
+            candidate = previous;
+        }
+    }
+    points.reverse();
+
+    let mut edges = vec![points[0].candidate.edge];
+    for route in routes.into_iter().rev() {
+        edges.extend_from_slice(route);
+    }
+    Match {source: points[0].candidate.source, edges: edges, points: points}
+}
+
+// Matches a GPS trace to the network with a hidden Markov model decoded by the Viterbi algorithm.
+// The candidates of a point are the closest points of the edges around it, more likely the
+// closer they are. Transitions between the candidates of consecutive points are more likely the
+// closer the length of the route between them is to the great circle distance of the points.
+// Points without candidates are skipped. If no candidate of a point can be reached from the
+// previous one on a route of at most max_detour times their distance, the trace is split and
+// matched in several parts.
+pub fn match_trace<H: AddressableHeap<FloatWeight, Handle=Node>>(network: &RoadNetwork, index: &SegmentIndex, heap: &mut H, trace: &[WGS84], options: &MatchingOptions) -> Result<Vec<Match>, SearchError> {
+    let mut matches = Vec::new();
+    let mut steps : Vec<Step> = Vec::new();
+    let mut tree = Tree {parents: HashMap::new(), distances: HashMap::new()};
+
+    for (point_index, point) in trace.iter().enumerate() {
+        let mut candidates = index.nearest_edges(point, options.radius);
+        candidates.truncate(options.max_candidates);
+        if candidates.is_empty() {
+            continue;
+        }
+        let emissions : Vec<f64> = candidates.iter().map(|candidate| -0.5 * (candidate.distance / options.sigma).powi(2)).collect();
+        let mut scores = vec![f64::NEG_INFINITY; candidates.len()];
+        let mut previous = vec![None; candidates.len()];
+
+        match steps.last() {
+            None => scores = emissions.clone(),
+            Some(last) => {
+                let great_circle = haversine(&trace[last.index], point);
+                let max_distance = great_circle * options.max_detour + 2.0 * options.radius;
+                for (from, last_candidate) in last.candidates.iter().enumerate() {
+                    let routes = try!(routes(network, heap, &mut tree, last_candidate, &candidates, max_distance));
+                    for (to, route) in routes.into_iter().enumerate() {
+                        if let Some((distance, edges)) = route {
+                            let score = last.scores[from] - (distance - great_circle).abs() / options.beta + emissions[to];
+                            if score > scores[to] {
+                                scores[to] = score;
+                                previous[to] = Some((from, edges));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if scores.iter().all(|&score| score == f64::NEG_INFINITY) {
+            matches.push(decode(&steps));
+            steps.clear();
+            scores = emissions;
+        }
+        steps.push(Step {index: point_index, candidates: candidates, scores: scores, previous: previous});
+    }
+
+    if !steps.is_empty() {
+        matches.push(decode(&steps));
+    }
+    Ok(matches)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// value of an attribute like name="value" in the text of a tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    tag.split_whitespace().filter_map(|token| {
+        let mut parts = token.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if key == name => Some(value.trim_end_matches('/').trim_matches(|c| c == '"' || c == '\'')),
+            _ => None
+        }
+    }).next()
+}
+
+// Reads the track points of a GPX file in order, all tracks and segments are concatenated
+pub fn read_gpx<R: Read>(mut reader: R) -> io::Result<Vec<WGS84>> {
+    let mut text = String::new();
+    try!(reader.read_to_string(&mut text));
+
+    let mut points = Vec::new();
+    for part in text.split("<trkpt").skip(1) {
+        let tag = match part.find('>') {
+            Some(end) => &part[..end],
+            None => return Err(invalid_data("unterminated trkpt"))
+        };
+        let lat = attribute(tag, "lat").and_then(|value| value.parse::<f64>().ok());
+        let lon = attribute(tag, "lon").and_then(|value| value.parse::<f64>().ok());
+        match (lat, lon) {
+            (Some(lat), Some(lon)) => points.push(WGS84 {lon: lon, lat: lat}),
+            _ => return Err(invalid_data("trkpt without numeric lat and lon"))
+        }
+    }
+    Ok(points)
+}
+
+// Writes one track with a segment per line, e.g. the geometries of the matches of a trace
+pub fn write_gpx<W: Write>(writer: &mut W, lines: &[Vec<WGS84>]) -> io::Result<()> {
+    try!(writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    try!(writeln!(writer, "<gpx version=\"1.1\" creator=\"shortestpath\">"));
+    try!(writeln!(writer, "  <trk>"));
+    for line in lines {
+        try!(writeln!(writer, "    <trkseg>"));
+        for point in line {
+            try!(writeln!(writer, "      <trkpt lat=\"{}\" lon=\"{}\"/>", point.lat, point.lon));
+        }
+        try!(writeln!(writer, "    </trkseg>"));
+    }
+    try!(writeln!(writer, "  </trk>"));
+    writeln!(writer, "</gpx>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressable_heap::AddressableBinaryHeap;
+    use graph_builder::*;
+
+    use std::fs::File;
+
+    // two parallel one way roads to the east, joined by two one way roads to the north
+    fn network() -> RoadNetwork {
+        load_geojson(&String::from("data/matching.geojson"), &LoadOptions::default()).unwrap()
+    }
+
+    fn edge(network: &RoadNetwork, from: i64, to: i64) -> Edge {
+        let source = network.id_map[&from];
+        network.graph.edges(source).find(|&edge| network.graph.target(edge) == network.id_map[&to]).unwrap()
+    }
+
+    #[test]
+    fn gpx() {
+        let trace = read_gpx(File::open("data/trace.gpx").unwrap()).unwrap();
+        assert_eq!(trace.len(), 9);
+        assert_eq!(trace[0], WGS84 {lon: 7.0002, lat: 43.00002});
+        assert_eq!(trace[2], WGS84 {lon: 7.0014, lat: 43.00003});
+        assert_eq!(trace[3], WGS84 {lon: 7.0019, lat: 42.99998});
+
+        let closed = read_gpx("<trkseg><trkpt lon=\"7.5\" lat=\"43.5\"/></trkseg>".as_bytes()).unwrap();
+        assert_eq!(closed, vec![WGS84 {lon: 7.5, lat: 43.5}]);
+        for text in &["<trkpt lat=\"43\">", "<trkpt lat=\"north\" lon=\"7\">", "<trkpt lat=\"43\" lon=\"7\""] {
+            assert_eq!(read_gpx(text.as_bytes()).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+
+        let mut output = Vec::new();
+        write_gpx(&mut output, &[trace.clone(), vec![WGS84 {lon: 7.5, lat: 43.5}]]).unwrap();
+        let mut written = trace.clone();
+        written.push(WGS84 {lon: 7.5, lat: 43.5});
+        assert_eq!(read_gpx(&output[..]).unwrap(), written);
+    }
+
+    #[test]
+    fn match_sample_trace() {
+        let network = network();
+        let index = SegmentIndex::new(&network, 0.001);
+        let mut heap = AddressableBinaryHeap::new(network.graph.num_nodes());
+        let trace = read_gpx(File::open("data/trace.gpx").unwrap()).unwrap();
+        let matches = match_trace(&network, &index, &mut heap, &trace, &MatchingOptions::default()).unwrap();
+
+        // along the southern road, north at the first junction and east on the northern road
+        assert_eq!(matches.len(), 1);
+        let ref matched = matches[0];
+        assert_eq!(matched.source, network.id_map[&1]);
+        assert_eq!(matched.edges, vec![edge(&network, 1, 2), edge(&network, 2, 3), edge(&network, 3, 8), edge(&network, 8, 9), edge(&network, 9, 10)]);
+        assert_eq!(matched.points.iter().map(|point| point.index).collect::<Vec<_>>(), (0..9).collect::<Vec<_>>());
+        assert_eq!(matched.points[4].candidate.edge, edge(&network, 3, 8));
+        assert_eq!(matched.points[7].candidate.edge, edge(&network, 9, 10));
+        for point in &matched.points {
+            assert!(point.candidate.distance < 10.0);
+        }
+
+        let geometry = matched.geometry(&network);
+        assert_eq!(geometry.first(), Some(&WGS84 {lon: 7.0, lat: 43.0}));
+        assert_eq!(geometry.last(), Some(&WGS84 {lon: 7.004, lat: 43.001}));
+    }
+
+    #[test]
+    fn split_trace() {
+        let network = network();
+        let index = SegmentIndex::new(&network, 0.001);
+        let mut heap = AddressableBinaryHeap::new(network.graph.num_nodes());
+        // back on the southern road, which can not be reached from the northern one,
+        // the point in between is too far from every road
+        let trace = vec![WGS84 {lon: 7.0005, lat: 43.001}, WGS84 {lon: 7.0015, lat: 43.00102}, WGS84 {lon: 7.0, lat: 43.0005},
+                         WGS84 {lon: 7.0035, lat: 43.0}];
+        let matches = match_trace(&network, &index, &mut heap, &trace, &MatchingOptions::default()).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].edges, vec![edge(&network, 6, 7), edge(&network, 7, 8)]);
+        assert_eq!(matches[0].points.len(), 2);
+        assert_eq!(matches[1].edges, vec![edge(&network, 4, 5)]);
+        assert_eq!(matches[1].points[0].index, 3);
+
+        assert_eq!(match_trace(&network, &index, &mut heap, &[], &MatchingOptions::default()), Ok(vec![]));
+    }
+}
@@ -0,0 +1,274 @@
+use addressable_heap::AddressableHeap;
+use handle_map::{HandleMap, DenseHandleMap, NOT_INSERTED, REMOVED};
+
+const NIL : u32 = u32::max_value();
+
+// Nodes are stored in an arena, links are arena indices.
+// prev points to the parent for the leftmost child and to the left sibling otherwise.
+struct PairingNode<Key> {
+    key: Key,
+    handle: u32,
+    child: u32,
+    sibling: u32,
+    prev: u32
+}
+
+pub struct AddressablePairingHeap<Key, M: HandleMap = DenseHandleMap> {
+    nodes: Vec<PairingNode<Key>>,
+    root: u32,
+    len: usize,
+    handle_to_index: M
+}
+
+impl<Key: Copy + Ord> AddressablePairingHeap<Key> {
+    pub fn new(num_handles: usize) -> AddressablePairingHeap<Key> {
+        AddressablePairingHeap::with_handle_map(DenseHandleMap::new(num_handles))
+    }
+}
+
+impl<Key: Copy + Ord, M: HandleMap> AddressablePairingHeap<Key, M> {
+    pub fn with_handle_map(handle_to_index: M) -> AddressablePairingHeap<Key, M> {
+        AddressablePairingHeap {nodes: vec![], root: NIL, len: 0, handle_to_index: handle_to_index}
+    }
+
+    // links two trees, the root with the bigger key becomes the first child of the other
+    fn meld(&mut self, first: u32, second: u32) -> u32 {
+        if first == NIL {
+            return second;
+        }
+        if second == NIL {
+            return first;
+        }
+
+        let (parent, child) = if self.nodes[second as usize].key < self.nodes[first as usize].key {
+            (second, first)
+        } else {
+            (first, second)
+        };
+
+        let first_child = self.nodes[parent as usize].child;
+        if first_child != NIL {
+            self.nodes[first_child as usize].prev = child;
+        }
+        self.nodes[child as usize].sibling = first_child;
+        self.nodes[child as usize].prev = parent;
+        self.nodes[parent as usize].child = child;
+        parent
+    }
+
+    // removes the subtree at index from its parent
+    fn cut(&mut self, index: u32) {
+        let prev = self.nodes[index as usize].prev;
+        let sibling = self.nodes[index as usize].sibling;
+        if self.nodes[prev as usize].child == index {
+            self.nodes[prev as usize].child = sibling;
+        } else {
+            self.nodes[prev as usize].sibling = sibling;
+        }
+        if sibling != NIL {
+            self.nodes[sibling as usize].prev = prev;
+        }
+        self.nodes[index as usize].sibling = NIL;
+        self.nodes[index as usize].prev = NIL;
+    }
+
+    // takes the node at index out of the heap, its children are melded with the root
+    fn detach(&mut self, index: u32) {
+        let first_child = self.nodes[index as usize].child;
+        self.nodes[index as usize].child = NIL;
+        let children = self.merge_children(first_child);
+        if index == self.root {
+            self.root = children;
+        } else {
+            self.cut(index);
+            let root = self.root;
+            self.root = self.meld(root, children);
+        }
+    }
+
+    // two-pass pairing of the children of a removed root
+    fn merge_children(&mut self, first_child: u32) -> u32 {
+        let mut pairs = Vec::new();
+        let mut current = first_child;
+        while current != NIL {
+            let first = current;
+            let second = self.nodes[first as usize].sibling;
+            self.nodes[first as usize].sibling = NIL;
+            self.nodes[first as usize].prev = NIL;
+            if second == NIL {
+                pairs.push(first);
+                break;
+            }
+            current = self.nodes[second as usize].sibling;
+            self.nodes[second as usize].sibling = NIL;
+            self.nodes[second as usize].prev = NIL;
+            let pair = self.meld(first, second);
+            pairs.push(pair);
+        }
+
+        let mut root = NIL;
+        for &pair in pairs.iter().rev() {
+            root = self.meld(root, pair);
+        }
+        root
+    }
+}
+
+impl<Key: Copy + Ord, M: HandleMap> AddressableHeap<Key> for AddressablePairingHeap<Key, M> {
+    type Handle = u32;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn min(&self) -> Option<(Self::Handle, Key)> {
+        if self.root == NIL {
+            None
+        } else {
+            let ref root = self.nodes[self.root as usize];
+            Some((root.handle, root.key))
+        }
+    }
+
+    fn key(&self, handle: Self::Handle) -> Key {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        self.nodes[self.handle_to_index.get(handle) as usize].key
+    }
+
+    fn push(&mut self, handle: Self::Handle, key: Key) {
+        let index = self.nodes.len() as u32;
+        self.nodes.push(PairingNode {key: key, handle: handle, child: NIL, sibling: NIL, prev: NIL});
+        self.handle_to_index.set(handle, index);
+        let root = self.root;
+        self.root = self.meld(root, index);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(Self::Handle, Key)> {
+        if self.root == NIL {
+            return None;
+        }
+
+        let (handle, key, first_child) = {
+            let ref root = self.nodes[self.root as usize];
+            (root.handle, root.key, root.child)
+        };
+        self.root = self.merge_children(first_child);
+        self.handle_to_index.set(handle, REMOVED);
+        self.len -= 1;
+        Some((handle, key))
+    }
+
+    fn decrease(&mut self, handle: Self::Handle, key: Key) {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle);
+        if self.nodes[index as usize].key <= key {
+            return;
+        }
+
+        self.nodes[index as usize].key = key;
+        if index != self.root {
+            self.cut(index);
+            let root = self.root;
+            self.root = self.meld(root, index);
+        }
+    }
+
+    fn increase(&mut self, handle: Self::Handle, key: Key) {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle);
+        if self.nodes[index as usize].key >= key {
+            return;
+        }
+
+        // the children may now be smaller, so they are detached and melded back
+        self.detach(index);
+        self.nodes[index as usize].key = key;
+        let root = self.root;
+        self.root = self.meld(root, index);
+    }
+
+    fn remove(&mut self, handle: Self::Handle) -> Key {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let index = self.handle_to_index.get(handle);
+        self.detach(index);
+        self.handle_to_index.set(handle, REMOVED);
+        self.len -= 1;
+        self.nodes[index as usize].key
+    }
+
+    fn in_heap(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) < REMOVED
+    }
+
+    fn was_inserted(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) != NOT_INSERTED
+    }
+
+    fn clear(&mut self) {
+        self.handle_to_index.clear();
+        self.nodes.clear();
+        self.root = NIL;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressable_heap::AddressableHeap;
+
+    #[test]
+    fn push_pop_sorted() {
+        let mut h : AddressablePairingHeap<u32> = AddressablePairingHeap::new(20);
+        let keys = [7, 3, 19, 0, 4, 4, 12, 9, 1, 15, 2];
+        for (handle, &key) in keys.iter().enumerate() {
+            h.push(handle as u32, key);
+        }
+        let mut sorted = keys.to_vec();
+        sorted.sort();
+        for key in sorted {
+            assert_eq!(h.pop().map(|(_, k)| k), Some(key));
+        }
+        assert_eq!(h.pop(), None);
+        assert_eq!(h.len(), 0);
+    }
+
+    #[test]
+    fn decrease_key() {
+        let mut h : AddressablePairingHeap<u32> = AddressablePairingHeap::new(10);
+        for handle in 0..10 {
+            h.push(handle, 10 + handle);
+        }
+        // restructures the tree so decreased nodes are inner nodes
+        assert_eq!(h.pop(), Some((0, 10)));
+        h.decrease(7, 1);
+        assert_eq!(h.min(), Some((7, 1)));
+        h.decrease(9, 2);
+        // should be a noop
+        h.decrease(3, 20);
+        assert_eq!(h.key(3), 13);
+        assert_eq!(h.pop(), Some((7, 1)));
+        assert_eq!(h.pop(), Some((9, 2)));
+        assert_eq!(h.pop(), Some((1, 11)));
+    }
+
+    #[test]
+    fn increase_and_remove() {
+        let mut h : AddressablePairingHeap<u32> = AddressablePairingHeap::new(10);
+        for handle in 0..10 {
+            h.push(handle, handle);
+        }
+        assert_eq!(h.pop(), Some((0, 0)));
+        // 1 is the root, its children have to be melded back
+        h.increase(1, 20);
+        assert_eq!(h.min(), Some((2, 2)));
+        h.increase(6, 15);
+        assert_eq!(h.remove(4), 4);
+        assert_eq!(h.remove(2), 2);
+        assert!(!h.in_heap(2));
+        assert_eq!(h.len(), 7);
+        let keys : Vec<u32> = (0..7).map(|_| h.pop().unwrap().1).collect();
+        assert_eq!(keys, vec![3, 5, 7, 8, 9, 15, 20]);
+    }
+}
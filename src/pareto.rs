@@ -0,0 +1,142 @@
+use graph::{Graph, Node, Edge};
+use label_setting::{LabelProblem, Stop, label_setting};
+use path::Path;
+use search::SearchError;
+use weight::Weight;
+
+use std::marker::PhantomData;
+
+pub trait CriteriaData {
+    // one cost per criterion, all of them are minimized
+    fn costs(&self) -> &[u32];
+}
+
+// a dominates b if it is nowhere worse
+fn dominates(a: &[u32], b: &[u32]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a <= b)
+}
+
+struct Criteria<'a, D: 'a, G: 'a> {
+    graph: &'a G,
+    num_criteria: usize,
+    data: PhantomData<D>
+}
+
+impl<'a, D: CriteriaData, G: Graph<D, N=Node, E=Edge>> LabelProblem for Criteria<'a, D, G> {
+    type Label = Vec<u32>;
+    type Step = Edge;
+
+    fn dominates(&self, a: &Vec<u32>, b: &Vec<u32>) -> bool {
+        dominates(a, b)
+    }
+
+    fn extend(&mut self, node: Node, costs: &Vec<u32>, successors: &mut Vec<(Node, Vec<u32>, Edge)>) -> Result<(), SearchError> {
+        for edge in self.graph.edges(node) {
+            let edge_costs = self.graph.data(edge).costs();
+            assert_eq!(edge_costs.len(), self.num_criteria, "Edge {} has {} costs instead of {}", edge, edge_costs.len(), self.num_criteria);
+            if edge_costs.iter().any(|&cost| cost == u32::infinity()) {
+                continue;
+            }
+            let mut new_costs = Vec::with_capacity(self.num_criteria);
+            for (cost, &edge_cost) in costs.iter().zip(edge_costs) {
+                match Weight::checked_add(*cost, edge_cost) {
+                    Some(new_cost) => new_costs.push(new_cost),
+                    None => return Err(SearchError::Overflow(node, edge))
+                }
+            }
+            successors.push((self.graph.target(edge), new_costs, edge));
+        }
+        Ok(())
+    }
+}
+
+// All Pareto optimal paths from source to target, sorted lexicographically by their costs.
+// Labels are extended in lexicographic order and, as costs only grow along a path, labels
+// dominated by one at the target are pruned as well.
+// Every edge has to have num_criteria costs, edges with an infinite cost are closed.
+pub fn pareto_search<D: CriteriaData, G: Graph<D, N=Node, E=Edge>>(graph: &G, num_criteria: usize, source: Node, target: Node) -> Result<Vec<Path<Vec<u32>>>, SearchError> {
+    let mut criteria = Criteria {graph: graph, num_criteria: num_criteria, data: PhantomData};
+    let labels = try!(label_setting(&mut criteria, graph.num_nodes(), source, target, vec![0; num_criteria], Stop::Front));
+    let mut paths : Vec<Path<Vec<u32>>> = labels.bag(target).iter().map(|&label| labels.path(label, labels.label(label).clone())).collect();
+    paths.sort_by(|a, b| a.weight.cmp(&b.weight));
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use random::XorShift;
+    use test_util::{CostData, cost_graph, random_cost_graph, simple_path_costs};
+
+    impl CriteriaData for CostData {
+        fn costs(&self) -> &[u32] {
+            &self.costs
+        }
+    }
+
+    fn costs(paths: &[Path<Vec<u32>>]) -> Vec<Vec<u32>> {
+        paths.iter().map(|path| path.weight.clone()).collect()
+    }
+
+    // costs of all simple paths that no other simple path dominates
+    fn brute_force_front(g: &AdjArrayGraph<CostData>, source: Node, target: Node) -> Vec<Vec<u32>> {
+        let all = simple_path_costs(g, source, target);
+        let mut front : Vec<Vec<u32>> = all.iter()
+            .filter(|a| !all.iter().any(|b| dominates(b, &a[..]) && b != *a))
+            .map(|costs| costs.to_vec()).collect();
+        front.sort();
+        front.dedup();
+        front
+    }
+
+    #[test]
+    fn time_and_climb() {
+        // 0 -> 5 over 1 is fast but steep, over 3 it is flat but slow, over 2 in between,
+        // the direct edge is dominated and the one over 4 is closed
+        let g = cost_graph(&[(0, 1, 2, 5), (1, 5, 2, 5), (0, 2, 2, 3), (2, 5, 3, 3), (0, 3, 3, 1), (3, 5, 3, 1),
+                        (0, 5, 10, 10), (0, 4, 1, 0), (4, 5, u32::max_value(), 0)]);
+        let paths = pareto_search(&g, 2, 0, 5).unwrap();
+        assert_eq!(costs(&paths), vec![vec![4, 10], vec![5, 6], vec![6, 2]]);
+        assert_eq!(paths.iter().map(|path| path.nodes.clone()).collect::<Vec<_>>(), vec![vec![0, 1, 5], vec![0, 2, 5], vec![0, 3, 5]]);
+    }
+
+    #[test]
+    fn random_graphs() {
+        let mut random = XorShift::new(42);
+        for _ in 0..20 {
+            let g = random_cost_graph(&mut random, 8, 20);
+            let paths = pareto_search(&g, 2, 0, 7).unwrap();
+            assert_eq!(costs(&paths), brute_force_front(&g, 0, 7));
+            for path in &paths {
+                let mut sum = vec![0, 0];
+                for &edge in &path.edges {
+                    sum[0] += g.data(edge).costs[0];
+                    sum[1] += g.data(edge).costs[1];
+                }
+                assert_eq!(sum, path.weight);
+                assert_eq!(path.edges.len() + 1, path.nodes.len());
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable() {
+        let g = cost_graph(&[(0, 1, 1, 1), (2, 1, 1, 1)]);
+        assert_eq!(pareto_search(&g, 2, 0, 2), Ok(vec![]));
+        assert_eq!(pareto_search(&g, 2, 0, 0), Ok(vec![Path {weight: vec![0, 0], nodes: vec![0], edges: vec![]}]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn wrong_number_of_costs() {
+        let g = cost_graph(&[(0, 1, 1, 1)]);
+        pareto_search(&g, 3, 0, 1).unwrap();
+    }
+
+    #[test]
+    fn overflow() {
+        let g = cost_graph(&[(0, 1, 1, 1), (1, 2, 1, u32::max_value() - 1)]);
+        assert_eq!(pareto_search(&g, 2, 0, 2), Err(SearchError::Overflow(1, 1)));
+    }
+}
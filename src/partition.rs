@@ -0,0 +1,427 @@
+use graph::{Graph, Node, Edge};
+use renumbering::sort_along_longer_side;
+use wgs84::WGS84;
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::io::{BufRead, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartitionError {
+    // the levels do not have a cell for the same number of nodes
+    UnequalLevels,
+    // (level, cell), ids of a level must be smaller than the number of nodes
+    CellOutOfRange(usize, u32),
+    // (level, cell), the cell lies in more than one cell of the next level
+    NotNested(usize, u32)
+}
+
+// Nested partition of the nodes, level 0 has the smallest cells and
+// every cell lies completely inside one cell of the next level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultilevelPartition {
+    // cells[level][node]
+    cells: Vec<Vec<u32>>,
+    num_cells: Vec<u32>
+}
+
+impl MultilevelPartition {
+    // cells[level][node] is the cell id of node on level, ids of a level must be dense
+    pub fn new(cells: Vec<Vec<u32>>) -> MultilevelPartition {
+        match MultilevelPartition::try_new(cells) {
+            Ok(partition) => partition,
+            Err(PartitionError::NotNested(level, cell)) => panic!("Cell {} on level {} is not nested in level {}", cell, level, level + 1),
+            Err(error) => panic!("Invalid partition: {:?}", error)
+        }
+    }
+
+    // like new, but returns an error for cells that are out of range or not nested
+    pub fn try_new(cells: Vec<Vec<u32>>) -> Result<MultilevelPartition, PartitionError> {
+        let num_nodes = cells.first().map_or(0, |level| level.len());
+        if cells.iter().any(|level| level.len() != num_nodes) {
+            return Err(PartitionError::UnequalLevels);
+        }
+        for (level, level_cells) in cells.iter().enumerate() {
+            if let Some(&cell) = level_cells.iter().find(|&&cell| cell as usize >= num_nodes) {
+                return Err(PartitionError::CellOutOfRange(level, cell));
+            }
+        }
+
+        let num_cells : Vec<u32> = cells.iter().map(|level| level.iter().max().map_or(0, |&max| max + 1)).collect();
+        for level in 1..cells.len() {
+            let mut parents = vec![u32::max_value(); num_cells[level - 1] as usize];
+            for (&child, &parent) in cells[level - 1].iter().zip(cells[level].iter()) {
+                if parents[child as usize] == u32::max_value() {
+                    parents[child as usize] = parent;
+                }
+                if parents[child as usize] != parent {
+                    return Err(PartitionError::NotNested(level - 1, child));
+                }
+            }
+        }
+        Ok(MultilevelPartition {cells: cells, num_cells: num_cells})
+    }
+
+    // Recursive bisection of the coordinates along the longer side of the bounding box.
+    pub fn from_coordinates(coordinates: &[WGS84], cell_sizes: &[usize]) -> MultilevelPartition {
+        MultilevelPartition::from_bisection(coordinates.len(), cell_sizes, |nodes| {
+            sort_along_longer_side(nodes, coordinates);
+            nodes.len() / 2
+        })
+    }
+
+    // Splits the nodes recursively until every part fits into a cell. cell_sizes holds the
+    // maximum number of nodes in a cell for every level and has to be increasing.
+    // split reorders the nodes and returns the size of the first part.
+    pub fn from_bisection<F: FnMut(&mut [Node]) -> usize>(num_nodes: usize, cell_sizes: &[usize], mut split: F) -> MultilevelPartition {
+        assert!(cell_sizes.windows(2).all(|sizes| sizes[0] <= sizes[1]), "Cell sizes have to be increasing");
+        let mut partition = MultilevelPartition {cells: vec![vec![0; num_nodes]; cell_sizes.len()], num_cells: vec![0; cell_sizes.len()]};
+        let mut nodes : Vec<Node> = (0..num_nodes as Node).collect();
+        partition.bisect(&mut nodes, cell_sizes, cell_sizes.len(), &mut split);
+        partition
+    }
+
+    // open_levels is the number of levels, starting at the finest, without a cell for these nodes yet
+    fn bisect<F: FnMut(&mut [Node]) -> usize>(&mut self, nodes: &mut [Node], cell_sizes: &[usize], mut open_levels: usize, split: &mut F) {
+        while open_levels > 0 && nodes.len() <= cell_sizes[open_levels - 1].max(1) {
+            open_levels -= 1;
+            for &node in nodes.iter() {
+                self.cells[open_levels][node as usize] = self.num_cells[open_levels];
+            }
+            self.num_cells[open_levels] += 1;
+        }
+        if open_levels == 0 {
+            return;
+        }
+
+        // both parts have to be non-empty, otherwise the recursion does not end
+        let middle = split(nodes).max(1).min(nodes.len() - 1);
+        let (left, right) = nodes.split_at_mut(middle);
+        self.bisect(left, cell_sizes, open_levels, split);
+        self.bisect(right, cell_sizes, open_levels, split);
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn num_cells(&self, level: usize) -> usize {
+        self.num_cells[level] as usize
+    }
+
+    pub fn cell(&self, level: usize, node: Node) -> u32 {
+        self.cells[level][node as usize]
+    }
+
+    pub fn cells(&self, level: usize) -> &[u32] {
+        &self.cells[level]
+    }
+}
+
+// number of edges between different cells of a level
+pub fn cut_edges<T, G: Graph<T, N=Node, E=Edge>>(graph: &G, partition: &MultilevelPartition, level: usize) -> usize {
+    let mut cut = 0;
+    for node in graph.nodes() {
+        for edge in graph.edges(node) {
+            if partition.cell(level, node) != partition.cell(level, graph.target(edge)) {
+                cut += 1;
+            }
+        }
+    }
+    cut
+}
+
+// One line per node with its cell on every level, separated by spaces
+pub fn write_partition<W: Write>(writer: &mut W, partition: &MultilevelPartition) -> io::Result<()> {
+    let num_nodes = partition.cells.first().map_or(0, |cells| cells.len());
+    for node in 0..num_nodes {
+        let line : Vec<String> = partition.cells.iter().map(|cells| cells[node].to_string()).collect();
+        try!(writeln!(writer, "{}", line.join(" ")));
+    }
+    Ok(())
+}
+
+pub fn read_partition<R: BufRead>(reader: R) -> io::Result<MultilevelPartition> {
+    let mut cells : Vec<Vec<u32>> = Vec::new();
+    for line in reader.lines() {
+        let line = try!(line);
+        let node_cells : Result<Vec<u32>, _> = line.split_whitespace().map(|cell| cell.parse::<u32>()).collect();
+        let node_cells = try!(node_cells.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "cell is not a number")));
+        if cells.is_empty() {
+            cells = vec![Vec::new(); node_cells.len()];
+        }
+        if node_cells.len() != cells.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "every node needs a cell on every level"));
+        }
+        for (level, cell) in node_cells.into_iter().enumerate() {
+            cells[level].push(cell);
+        }
+    }
+    MultilevelPartition::try_new(cells).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("invalid partition: {:?}", error)))
+}
+
+// Inertial flow, see Schild and Sommer, "On Balanced Separators in Road Networks".
+// The nodes are sorted along several directions and the first and last balance * n
+// nodes become sources and sinks of a unit capacity max-flow problem on the undirected
+// graph. The minimum cut over all directions separates the nodes.
+pub struct InertialFlow {
+    pub balance: f64
+}
+
+impl Default for InertialFlow {
+    fn default() -> InertialFlow {
+        InertialFlow {balance: 0.25}
+    }
+}
+
+// (lon, lat) factors of the directions the nodes are sorted along
+const DIRECTIONS : [(f64, f64); 4] = [(1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, -1.0)];
+
+// Residual network with capacity one in both directions of every undirected edge
+struct FlowNetwork {
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+    // index of the arc in the opposite direction
+    reverse: Vec<usize>,
+    capacity: Vec<u8>
+}
+
+impl FlowNetwork {
+    fn new(num_nodes: usize, edges: &[(u32, u32)]) -> FlowNetwork {
+        let mut degrees = vec![0; num_nodes + 1];
+        for &(a, b) in edges {
+            degrees[a as usize + 1] += 1;
+            degrees[b as usize + 1] += 1;
+        }
+        let mut offsets = degrees;
+        for node in 0..num_nodes {
+            offsets[node + 1] += offsets[node];
+        }
+
+        let mut next = offsets.clone();
+        let mut targets = vec![0; 2 * edges.len()];
+        let mut reverse = vec![0; 2 * edges.len()];
+        for &(a, b) in edges {
+            let (forward, backward) = (next[a as usize], next[b as usize]);
+            next[a as usize] += 1;
+            next[b as usize] += 1;
+            targets[forward] = b;
+            targets[backward] = a;
+            reverse[forward] = backward;
+            reverse[backward] = forward;
+        }
+        FlowNetwork {offsets: offsets, targets: targets, reverse: reverse, capacity: vec![1; 2 * edges.len()]}
+    }
+
+    fn num_nodes(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    // Augments shortest paths from any source to any sink until there is none left
+    // or the flow reaches limit, returns the flow.
+    fn max_flow(&mut self, is_source: &[bool], is_sink: &[bool], limit: usize) -> usize {
+        for capacity in self.capacity.iter_mut() {
+            *capacity = 1;
+        }
+
+        let mut flow = 0;
+        let mut parent_arc = vec![usize::max_value(); self.num_nodes()];
+        while flow < limit {
+            for arc in parent_arc.iter_mut() {
+                *arc = usize::max_value();
+            }
+            let mut queue : VecDeque<u32> = (0..self.num_nodes() as u32).filter(|&node| is_source[node as usize]).collect();
+            let mut sink = None;
+            'search: while let Some(node) = queue.pop_front() {
+                for arc in self.offsets[node as usize]..self.offsets[node as usize + 1] {
+                    let target = self.targets[arc];
+                    if self.capacity[arc] == 0 || is_source[target as usize] || parent_arc[target as usize] != usize::max_value() {
+                        continue;
+                    }
+                    parent_arc[target as usize] = arc;
+                    if is_sink[target as usize] {
+                        sink = Some(target);
+                        break 'search;
+                    }
+                    queue.push_back(target);
+                }
+            }
+
+            let mut node = match sink {
+                Some(sink) => sink,
+                None => break
+            };
+            while !is_source[node as usize] {
+                let arc = parent_arc[node as usize];
+                self.capacity[arc] -= 1;
+                self.capacity[self.reverse[arc]] += 1;
+                node = self.targets[self.reverse[arc]];
+            }
+            flow += 1;
+        }
+        flow
+    }
+
+    // nodes reachable from a source in the residual network
+    fn source_side(&self, is_source: &[bool]) -> Vec<bool> {
+        let mut reached = is_source.to_vec();
+        let mut queue : VecDeque<u32> = (0..self.num_nodes() as u32).filter(|&node| is_source[node as usize]).collect();
+        while let Some(node) = queue.pop_front() {
+            for arc in self.offsets[node as usize]..self.offsets[node as usize + 1] {
+                let target = self.targets[arc] as usize;
+                if self.capacity[arc] > 0 && !reached[target] {
+                    reached[target] = true;
+                    queue.push_back(target as u32);
+                }
+            }
+        }
+        reached
+    }
+}
+
+impl InertialFlow {
+    // Reorders the nodes so that the source side of the best cut comes first.
+    // Returns the size of the source side and the number of cut edges.
+    pub fn bisect<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, coordinates: &[WGS84], nodes: &mut [Node]) -> (usize, usize) {
+        let local : HashMap<Node, u32> = nodes.iter().enumerate().map(|(index, &node)| (node, index as u32)).collect();
+        let mut edges = Vec::new();
+        for (index, &node) in nodes.iter().enumerate() {
+            for edge in graph.edges(node) {
+                if let Some(&target) = local.get(&graph.target(edge)) {
+                    let index = index as u32;
+                    if index != target {
+                        edges.push((index.min(target), index.max(target)));
+                    }
+                }
+            }
+        }
+        edges.sort();
+        edges.dedup();
+        let mut network = FlowNetwork::new(nodes.len(), &edges);
+
+        let num_terminals = ((self.balance * nodes.len() as f64) as usize).max(1).min(nodes.len() / 2);
+        let mut best : Option<(usize, Vec<bool>)> = None;
+        for &(lon_factor, lat_factor) in DIRECTIONS.iter() {
+            let projection : Vec<f64> = nodes.iter().map(|&node| {
+                let ref coordinate = coordinates[node as usize];
+                lon_factor * coordinate.lon + lat_factor * coordinate.lat
+            }).collect();
+            let mut order : Vec<usize> = (0..nodes.len()).collect();
+            order.sort_by(|&a, &b| projection[a].partial_cmp(&projection[b]).unwrap_or(Ordering::Equal));
+
+            let mut is_source = vec![false; nodes.len()];
+            let mut is_sink = vec![false; nodes.len()];
+            for &index in &order[..num_terminals] {
+                is_source[index] = true;
+            }
+            for &index in &order[nodes.len() - num_terminals..] {
+                is_sink[index] = true;
+            }
+
+            let limit = best.as_ref().map_or(usize::max_value(), |best| best.0);
+            let flow = network.max_flow(&is_source, &is_sink, limit);
+            if flow < limit {
+                best = Some((flow, network.source_side(&is_source)));
+            }
+        }
+
+        let (cut, side) = best.unwrap();
+        let mut indices : Vec<usize> = (0..nodes.len()).collect();
+        // stable, so the source side keeps its order
+        indices.sort_by_key(|&index| !side[index]);
+        let reordered : Vec<Node> = indices.iter().map(|&index| nodes[index]).collect();
+        nodes.copy_from_slice(&reordered);
+        (side.iter().filter(|&&in_source| in_source).count(), cut)
+    }
+
+    pub fn partition<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, coordinates: &[WGS84], cell_sizes: &[usize]) -> MultilevelPartition {
+        MultilevelPartition::from_bisection(graph.num_nodes(), cell_sizes, |nodes| self.bisect(graph, coordinates, nodes).0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use test_util::{TestData, seeded_grid};
+
+    #[test]
+    fn nested_partition() {
+        let (_, coordinates) = seeded_grid(8, 8, 0);
+        let partition = MultilevelPartition::from_coordinates(&coordinates, &[4, 16]);
+        assert_eq!(partition.num_levels(), 2);
+        assert_eq!(partition.num_cells(0), 16);
+        assert_eq!(partition.num_cells(1), 4);
+        // validates the nesting
+        MultilevelPartition::new(partition.cells.clone());
+        // the lower left 2x2 square is a cell
+        assert_eq!(partition.cell(0, 0), partition.cell(0, 9));
+        assert!(partition.cell(0, 0) != partition.cell(0, 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_nested() {
+        MultilevelPartition::new(vec![vec![0, 0, 1], vec![0, 1, 1]]);
+    }
+
+    // two 4x4 grids side by side, connected by a single road
+    #[test]
+    fn bisect_bottleneck() {
+        let (grid_graph, mut coordinates) = seeded_grid(4, 4, 0);
+        let mut edges = grid_graph.into_edges();
+        for &(source, target, ref data) in edges.clone().iter() {
+            edges.push((source + 16, target + 16, data.clone()));
+        }
+        edges.push((3, 16, TestData {weight: 1}));
+        for index in 0..16 {
+            let coordinate = coordinates[index];
+            coordinates.push(WGS84 {lon: coordinate.lon + 4.0, lat: coordinate.lat});
+        }
+        let graph = AdjArrayGraph::new(edges);
+
+        let mut nodes : Vec<Node> = graph.nodes().collect();
+        let (size, cut) = InertialFlow::default().bisect(&graph, &coordinates, &mut nodes);
+        assert_eq!((size, cut), (16, 1));
+        let mut left = nodes[..16].to_vec();
+        left.sort();
+        assert_eq!(left, (0..16).collect::<Vec<Node>>());
+    }
+
+    #[test]
+    fn inertial_flow_partition() {
+        let (graph, coordinates) = seeded_grid(8, 8, 0);
+        let partition = InertialFlow::default().partition(&graph, &coordinates, &[16, 48]);
+        // the first cut is a straight line next to the sources through 8 rows, the one way
+        // rows 0, 3 and 6 have a single edge across it
+        assert_eq!(partition.num_cells(1), 2);
+        assert_eq!(cut_edges(&graph, &partition, 1), 13);
+        assert!(cut_edges(&graph, &partition, 0) > 13);
+        for cell in 0..partition.num_cells(0) as u32 {
+            assert!(partition.cells(0).iter().filter(|&&c| c == cell).count() <= 16);
+        }
+        MultilevelPartition::new(partition.cells.clone());
+    }
+
+    #[test]
+    fn write_and_read() {
+        let partition = MultilevelPartition::new(vec![vec![0, 1, 2, 2], vec![0, 0, 1, 1]]);
+        let mut buffer = Vec::new();
+        write_partition(&mut buffer, &partition).unwrap();
+        assert_eq!(String::from_utf8(buffer.clone()).unwrap(), "0 0\n1 0\n2 1\n2 1\n");
+        assert_eq!(read_partition(&buffer[..]).unwrap(), partition);
+        assert!(read_partition("0 0\n1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn read_invalid() {
+        let read = |text: &str| read_partition(text.as_bytes()).err().unwrap().kind();
+        // cell 0 lies in cell 0 and 1 of the next level
+        assert_eq!(read("0 0\n0 1\n"), io::ErrorKind::InvalidData);
+        assert_eq!(read("4294967295 0\n"), io::ErrorKind::InvalidData);
+        assert_eq!(MultilevelPartition::try_new(vec![vec![0, 0], vec![0, 1]]), Err(PartitionError::NotNested(0, 0)));
+        assert_eq!(MultilevelPartition::try_new(vec![vec![0, 2]]), Err(PartitionError::CellOutOfRange(0, 2)));
+        assert_eq!(MultilevelPartition::try_new(vec![vec![0, 1], vec![0]]), Err(PartitionError::UnequalLevels));
+    }
+}
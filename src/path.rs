@@ -0,0 +1,9 @@
+use graph::{Node, Edge};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path<K> {
+    pub weight: K,
+    // source and target included, one more than edges
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>
+}
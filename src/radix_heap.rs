@@ -0,0 +1,266 @@
+use addressable_heap::AddressableHeap;
+use handle_map::{HandleMap, DenseHandleMap, NOT_INSERTED, REMOVED};
+
+// one bucket for keys equal to the last minimum plus one for every bit
+const NUM_BUCKETS : usize = 33;
+
+#[derive(Clone, Copy)]
+struct Position {
+    bucket: u32,
+    index: u32
+}
+
+// Monotone radix heap for integer keys, see Ahuja et al.,
+// "Faster Algorithms for the Shortest Path Problem".
+// Keys that are pushed or decreased must not be smaller than the last popped key,
+// which always holds for dijkstra with non-negative weights.
+pub struct AddressableRadixHeap<M: HandleMap = DenseHandleMap> {
+    buckets: Vec<Vec<(u32, u32)>>,
+    last_min: u32,
+    len: usize,
+    // index into positions of every handle in the heap
+    handle_to_index: M,
+    positions: Vec<Position>
+}
+
+impl AddressableRadixHeap {
+    pub fn new(num_handles: usize) -> AddressableRadixHeap {
+        AddressableRadixHeap::with_handle_map(DenseHandleMap::new(num_handles))
+    }
+}
+
+impl<M: HandleMap> AddressableRadixHeap<M> {
+    pub fn with_handle_map(handle_to_index: M) -> AddressableRadixHeap<M> {
+        AddressableRadixHeap {
+            buckets: (0..NUM_BUCKETS).map(|_| Vec::new()).collect(),
+            last_min: 0,
+            len: 0,
+            handle_to_index: handle_to_index,
+            positions: vec![]
+        }
+    }
+
+    fn position(&self, handle: u32) -> Position {
+        self.positions[self.handle_to_index.get(handle) as usize]
+    }
+
+    fn set_position(&mut self, handle: u32, position: Position) {
+        if self.in_heap(handle) {
+            let index = self.handle_to_index.get(handle);
+            self.positions[index as usize] = position;
+        } else {
+            let index = self.positions.len() as u32;
+            self.positions.push(position);
+            self.handle_to_index.set(handle, index);
+        }
+    }
+
+    // index of the highest bit in which key differs from the last minimum
+    fn bucket_of(&self, key: u32) -> usize {
+        if key == self.last_min {
+            0
+        } else {
+            32 - (key ^ self.last_min).leading_zeros() as usize
+        }
+    }
+
+    fn insert(&mut self, handle: u32, key: u32) {
+        assert!(key >= self.last_min, "Key {} is smaller than the last minimum {}", key, self.last_min);
+        let bucket = self.bucket_of(key);
+        let position = Position {bucket: bucket as u32, index: self.buckets[bucket].len() as u32};
+        self.set_position(handle, position);
+        self.buckets[bucket].push((handle, key));
+    }
+
+    // takes the handle out of its bucket
+    fn unlink(&mut self, handle: u32) -> u32 {
+        let position = self.position(handle);
+        let (_, key) = self.buckets[position.bucket as usize].swap_remove(position.index as usize);
+        if let Some(&(moved, _)) = self.buckets[position.bucket as usize].get(position.index as usize) {
+            let moved_index = self.handle_to_index.get(moved);
+            self.positions[moved_index as usize].index = position.index;
+        }
+        key
+    }
+
+    // makes sure bucket 0 contains the minimum by redistributing
+    // the first non-empty bucket
+    fn refill(&mut self) {
+        if !self.buckets[0].is_empty() {
+            return;
+        }
+
+        let bucket = match (1..NUM_BUCKETS).find(|&b| !self.buckets[b].is_empty()) {
+            Some(bucket) => bucket,
+            None => return
+        };
+
+        let elements = ::std::mem::replace(&mut self.buckets[bucket], Vec::new());
+        self.last_min = elements.iter().map(|&(_, key)| key).min().unwrap();
+        // all keys now fall into smaller buckets
+        for &(handle, key) in &elements {
+            self.insert(handle, key);
+        }
+    }
+}
+
+impl<M: HandleMap> AddressableHeap<u32> for AddressableRadixHeap<M> {
+    type Handle = u32;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn min(&self) -> Option<(Self::Handle, u32)> {
+        match self.buckets.iter().find(|bucket| !bucket.is_empty()) {
+            None => None,
+            Some(bucket) => bucket.iter().min_by_key(|&&(_, key)| key).cloned()
+        }
+    }
+
+    fn key(&self, handle: Self::Handle) -> u32 {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let position = self.position(handle);
+        self.buckets[position.bucket as usize][position.index as usize].1
+    }
+
+    fn push(&mut self, handle: Self::Handle, key: u32) {
+        self.insert(handle, key);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(Self::Handle, u32)> {
+        self.refill();
+        match self.buckets[0].pop() {
+            None => None,
+            Some((handle, key)) => {
+                self.handle_to_index.set(handle, REMOVED);
+                self.len -= 1;
+                Some((handle, key))
+            }
+        }
+    }
+
+    fn decrease(&mut self, handle: Self::Handle, key: u32) {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        if self.key(handle) <= key {
+            return;
+        }
+        self.unlink(handle);
+        self.insert(handle, key);
+    }
+
+    fn increase(&mut self, handle: Self::Handle, key: u32) {
+        if self.key(handle) >= key {
+            return;
+        }
+        self.unlink(handle);
+        self.insert(handle, key);
+    }
+
+    fn remove(&mut self, handle: Self::Handle) -> u32 {
+        assert!(self.in_heap(handle), "Handle {} is not in the heap", handle);
+        let key = self.unlink(handle);
+        self.handle_to_index.set(handle, REMOVED);
+        self.len -= 1;
+        key
+    }
+
+    fn in_heap(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) < REMOVED
+    }
+
+    fn was_inserted(&self, handle: Self::Handle) -> bool {
+        self.handle_to_index.get(handle) != NOT_INSERTED
+    }
+
+    fn clear(&mut self) {
+        self.handle_to_index.clear();
+        self.positions.clear();
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        self.last_min = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressable_heap::AddressableHeap;
+    use handle_map::HashHandleMap;
+
+    #[test]
+    fn push_pop_sorted() {
+        let mut h = AddressableRadixHeap::new(20);
+        let keys = [7, 3, 19, 0, 4, 4, 12, 9, 1, 15, 2, 1 << 31];
+        for (handle, &key) in keys.iter().enumerate() {
+            h.push(handle as u32, key);
+        }
+        let mut sorted = keys.to_vec();
+        sorted.sort();
+        for key in sorted {
+            assert_eq!(h.min().map(|(_, k)| k), Some(key));
+            assert_eq!(h.pop().map(|(_, k)| k), Some(key));
+        }
+        assert_eq!(h.pop(), None);
+    }
+
+    #[test]
+    fn monotone_decrease() {
+        let mut h = AddressableRadixHeap::new(10);
+        h.push(0, 5);
+        h.push(1, 10);
+        h.push(2, 20);
+        assert_eq!(h.pop(), Some((0, 5)));
+        h.decrease(2, 6);
+        h.push(3, 5);
+        assert_eq!(h.pop(), Some((3, 5)));
+        assert_eq!(h.pop(), Some((2, 6)));
+        assert_eq!(h.pop(), Some((1, 10)));
+    }
+
+    #[test]
+    fn increase_and_remove() {
+        let mut h = AddressableRadixHeap::new(10);
+        h.push(0, 5);
+        h.push(1, 10);
+        h.push(2, 20);
+        h.push(3, 7);
+        assert_eq!(h.pop(), Some((0, 5)));
+        h.increase(3, 30);
+        assert_eq!(h.remove(1), 10);
+        assert!(!h.in_heap(1));
+        assert_eq!(h.len(), 2);
+        assert_eq!(h.pop(), Some((2, 20)));
+        assert_eq!(h.pop(), Some((3, 30)));
+        assert_eq!(h.pop(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn non_monotone_push() {
+        let mut h = AddressableRadixHeap::new(10);
+        h.push(0, 5);
+        h.pop();
+        h.push(1, 4);
+    }
+
+    #[test]
+    fn sparse_handles() {
+        let mut h = AddressableRadixHeap::with_handle_map(HashHandleMap::new());
+        h.push(4000000000, 3);
+        h.push(7, 1);
+        h.push(12, 2);
+        h.decrease(4000000000, 2);
+        assert_eq!(h.pop(), Some((7, 1)));
+        assert_eq!(h.pop().map(|(_, key)| key), Some(2));
+        assert_eq!(h.pop().map(|(_, key)| key), Some(2));
+        assert!(h.was_inserted(4000000000));
+        h.clear();
+        assert!(!h.was_inserted(4000000000));
+        h.push(12, 0);
+        assert_eq!(h.pop(), Some((12, 0)));
+    }
+}
@@ -0,0 +1,48 @@
+// xorshift64* generator, good enough for sampling queries and landmarks
+// and fully reproducible for a given seed
+pub struct XorShift {
+    state: u64
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // the state must never be zero
+        XorShift {state: if seed == 0 { 0x9E3779B97F4A7C15 } else { seed }}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(2685821657736338717)
+    }
+
+    // uniform-ish number in 0..bound
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    // uniform number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        let mut a = XorShift::new(42);
+        let mut b = XorShift::new(42);
+        for _ in 0..100 {
+            let value = a.next_below(10);
+            assert_eq!(value, b.next_below(10));
+            assert!(value < 10);
+            let f = a.next_f64();
+            assert_eq!(f, b.next_f64());
+            assert!(f >= 0.0 && f < 1.0);
+        }
+    }
+}
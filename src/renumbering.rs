@@ -0,0 +1,268 @@
+use graph::{Graph, Node, Edge};
+use wgs84::WGS84;
+
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+// Computes a new node order for a graph. The returned permutation
+// maps every old node id to its new id.
+pub trait Renumbering {
+    fn permutation<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, coordinates: &[WGS84]) -> Vec<Node>;
+}
+
+// Keeps the ids that were assigned while loading
+pub struct InputOrder;
+
+// Numbers nodes in the order a breadth-first search discovers them
+pub struct BfsOrder;
+
+// Numbers nodes in the order a depth-first search discovers them
+pub struct DfsOrder;
+
+// Sorts nodes along a Hilbert curve over their coordinates
+pub struct HilbertOrder;
+
+// Recursively bisects the coordinates until a cell has at most cell_size nodes,
+// nodes of the same cell get consecutive ids
+pub struct PartitionOrder {
+    pub cell_size: usize
+}
+
+// inverts a list of nodes given in their new order
+fn order_to_permutation(order: &[Node]) -> Vec<Node> {
+    let mut permutation = vec![0; order.len()];
+    for (new_id, &old_id) in order.iter().enumerate() {
+        permutation[old_id as usize] = new_id as Node;
+    }
+    permutation
+}
+
+impl Renumbering for InputOrder {
+    fn permutation<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, _: &[WGS84]) -> Vec<Node> {
+        graph.nodes().collect()
+    }
+}
+
+impl Renumbering for BfsOrder {
+    fn permutation<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, _: &[WGS84]) -> Vec<Node> {
+        let mut visited = vec![false; graph.num_nodes()];
+        let mut order = Vec::with_capacity(graph.num_nodes());
+        let mut queue = VecDeque::new();
+
+        // the graph is directed so we need to restart at every node not reached yet
+        for root in graph.nodes() {
+            if visited[root as usize] {
+                continue;
+            }
+            visited[root as usize] = true;
+            queue.push_back(root);
+
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+                for edge in graph.edges(node) {
+                    let target = graph.target(edge);
+                    if !visited[target as usize] {
+                        visited[target as usize] = true;
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+
+        order_to_permutation(&order)
+    }
+}
+
+impl Renumbering for DfsOrder {
+    fn permutation<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, _: &[WGS84]) -> Vec<Node> {
+        let mut visited = vec![false; graph.num_nodes()];
+        let mut order = Vec::with_capacity(graph.num_nodes());
+        let mut stack = Vec::new();
+
+        for root in graph.nodes() {
+            if visited[root as usize] {
+                continue;
+            }
+            stack.push(root);
+
+            while let Some(node) = stack.pop() {
+                if visited[node as usize] {
+                    continue;
+                }
+                visited[node as usize] = true;
+                order.push(node);
+                // push in reverse so the first edge is explored first
+                for edge in graph.edges(node).rev() {
+                    let target = graph.target(edge);
+                    if !visited[target as usize] {
+                        stack.push(target);
+                    }
+                }
+            }
+        }
+
+        order_to_permutation(&order)
+    }
+}
+
+const HILBERT_BITS : u32 = 16;
+
+// distance of the cell (x, y) along a Hilbert curve filling a 2^16 x 2^16 grid
+fn hilbert_index(mut x: u32, mut y: u32) -> u64 {
+    let n : u32 = 1 << HILBERT_BITS;
+    let mut d : u64 = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = if x & s > 0 { 1 } else { 0 };
+        let ry = if y & s > 0 { 1 } else { 0 };
+        d += (s as u64) * (s as u64) * ((3 * rx) ^ ry);
+        // rotate the quadrant so the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            let tmp = x;
+            x = y;
+            y = tmp;
+        }
+        s /= 2;
+    }
+    d
+}
+
+fn bounding_box(coordinates: &[WGS84], nodes: &[Node]) -> (WGS84, WGS84) {
+    let mut min = WGS84 {lon: 180.0, lat: 90.0};
+    let mut max = WGS84 {lon: -180.0, lat: -90.0};
+    for &node in nodes {
+        let ref c = coordinates[node as usize];
+        min.lon = min.lon.min(c.lon);
+        min.lat = min.lat.min(c.lat);
+        max.lon = max.lon.max(c.lon);
+        max.lat = max.lat.max(c.lat);
+    }
+    (min, max)
+}
+
+impl Renumbering for HilbertOrder {
+    fn permutation<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, coordinates: &[WGS84]) -> Vec<Node> {
+        let mut order : Vec<Node> = graph.nodes().collect();
+        let (min, max) = bounding_box(coordinates, &order);
+        let cells = ((1u32 << HILBERT_BITS) - 1) as f64;
+        let scale = |value: f64, min: f64, max: f64| -> u32 {
+            if max > min {
+                ((value - min) / (max - min) * cells).round() as u32
+            } else {
+                0
+            }
+        };
+
+        let keys : Vec<u64> = order.iter().map(|&node| {
+            let ref c = coordinates[node as usize];
+            hilbert_index(scale(c.lon, min.lon, max.lon), scale(c.lat, min.lat, max.lat))
+        }).collect();
+        order.sort_by_key(|&node| keys[node as usize]);
+
+        order_to_permutation(&order)
+    }
+}
+
+// Sorts the nodes along the longer side of their bounding box,
+// splitting the result in the middle gives a balanced bisection.
+pub fn sort_along_longer_side(nodes: &mut [Node], coordinates: &[WGS84]) {
+    let (min, max) = bounding_box(coordinates, nodes);
+    let split_lon = max.lon - min.lon > max.lat - min.lat;
+    nodes.sort_by(|&a, &b| {
+        let (ref ca, ref cb) = (coordinates[a as usize], coordinates[b as usize]);
+        let ordering = if split_lon { ca.lon.partial_cmp(&cb.lon) } else { ca.lat.partial_cmp(&cb.lat) };
+        ordering.unwrap_or(Ordering::Equal)
+    });
+}
+
+fn bisect(nodes: &mut [Node], coordinates: &[WGS84], cell_size: usize) {
+    if nodes.len() <= cell_size {
+        return;
+    }
+
+    sort_along_longer_side(nodes, coordinates);
+    let middle = nodes.len() / 2;
+    let (left, right) = nodes.split_at_mut(middle);
+    bisect(left, coordinates, cell_size);
+    bisect(right, coordinates, cell_size);
+}
+
+impl Renumbering for PartitionOrder {
+    fn permutation<T, G: Graph<T, N=Node, E=Edge>>(&self, graph: &G, coordinates: &[WGS84]) -> Vec<Node> {
+        let mut order : Vec<Node> = graph.nodes().collect();
+        bisect(&mut order, coordinates, self.cell_size.max(1));
+        order_to_permutation(&order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+
+    fn is_permutation(permutation: &Vec<Node>) -> bool {
+        let mut sorted = permutation.clone();
+        sorted.sort();
+        sorted.iter().enumerate().all(|(idx, &id)| idx as Node == id)
+    }
+
+    // 0 -> 3 -> 1
+    // |
+    // v
+    // 2 -> 4
+    fn test_graph() -> (AdjArrayGraph<()>, Vec<WGS84>) {
+        let graph = AdjArrayGraph::new(vec![(0, 3, ()), (0, 2, ()), (3, 1, ()), (2, 4, ())]);
+        let coordinates = vec![
+            WGS84 {lon: 0.0, lat: 0.0},
+            WGS84 {lon: 2.0, lat: 0.0},
+            WGS84 {lon: 0.0, lat: 1.0},
+            WGS84 {lon: 1.0, lat: 0.0},
+            WGS84 {lon: 1.0, lat: 1.0},
+        ];
+        (graph, coordinates)
+    }
+
+    #[test]
+    fn bfs_order() {
+        let (graph, coordinates) = test_graph();
+        let permutation = BfsOrder.permutation(&graph, &coordinates);
+        // visited as 0, 2, 3, 4, 1
+        assert_eq!(permutation, vec![0, 4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn dfs_order() {
+        let (graph, coordinates) = test_graph();
+        let permutation = DfsOrder.permutation(&graph, &coordinates);
+        // visited as 0, 2, 4, 3, 1
+        assert_eq!(permutation, vec![0, 4, 1, 3, 2]);
+    }
+
+    #[test]
+    fn hilbert_curve() {
+        // the curve of order 1 visits (0,0), (0,1), (1,1), (1,0)
+        let max = (1 << HILBERT_BITS) - 1;
+        assert_eq!(hilbert_index(0, 0), 0);
+        assert!(hilbert_index(0, max) < hilbert_index(max, max));
+        assert!(hilbert_index(max, max) < hilbert_index(max, 0));
+
+        let (graph, coordinates) = test_graph();
+        let permutation = HilbertOrder.permutation(&graph, &coordinates);
+        assert!(is_permutation(&permutation));
+        assert_eq!(permutation[0], 0);
+    }
+
+    #[test]
+    fn partition_order() {
+        let (graph, coordinates) = test_graph();
+        let permutation = PartitionOrder {cell_size: 2}.permutation(&graph, &coordinates);
+        assert!(is_permutation(&permutation));
+        // the two west-most nodes form the first cell
+        assert_eq!(permutation[0], 0);
+        assert_eq!(permutation[2], 1);
+    }
+}
@@ -1,38 +1,207 @@
 use addressable_heap::AddressableHeap;
 use graph::{Graph, Node, Edge};
-use std::ops::{Add};
+use weight::Weight;
 
 pub trait WeightedData<K> {
     fn weight(&self) -> K;
 }
 
-pub fn dijkstra<K: Copy + Ord + Add<Output=K> + From<u32>, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<K, Handle=Node>>(graph: &G, heap: &mut H, source: Node, target: Node) -> Option<K> {
-    heap.push(source, K::from(0));
+// Callbacks invoked during a search. Every method defaults to a no-op,
+// so unused callbacks are optimized away.
+pub trait SearchVisitor<K> {
+    // node was popped from the heap, weight is final
+    fn settle(&mut self, _node: Node, _weight: K) {}
+    // edge from source to target was scanned
+    fn relax(&mut self, _source: Node, _edge: Edge, _target: Node, _weight: K) {}
+    // target was reached for the first time
+    fn push(&mut self, _source: Node, _edge: Edge, _target: Node, _weight: K) {}
+    // a shorter path to target was found
+    fn decrease(&mut self, _source: Node, _edge: Edge, _target: Node, _weight: K) {}
+}
+
+pub struct NoVisitor;
+
+impl<K> SearchVisitor<K> for NoVisitor {}
+
+// Counts the work done by a search
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub settled: usize,
+    pub relaxed: usize,
+    pub decreased: usize
+}
+
+impl SearchStats {
+    pub fn add(&mut self, other: &SearchStats) {
+        self.settled += other.settled;
+        self.relaxed += other.relaxed;
+        self.decreased += other.decreased;
+    }
+}
+
+impl<K> SearchVisitor<K> for SearchStats {
+    fn settle(&mut self, _: Node, _: K) {
+        self.settled += 1;
+    }
+
+    fn relax(&mut self, _: Node, _: Edge, _: Node, _: K) {
+        self.relaxed += 1;
+    }
+
+    fn decrease(&mut self, _: Node, _: Edge, _: Node, _: K) {
+        self.decreased += 1;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchError {
+    // the weight of a path over the edge leaving the node does not fit into the weight type,
+    // continuing with a saturated or wrapped weight would return a wrong route
+    Overflow(Node, Edge),
+    // same for a shortcut of an overlay graph from the first to the second node
+    ShortcutOverflow(Node, Node)
+}
+
+// Edges with an infinite weight are skipped.
+// The heap is cleared before the search starts, so it can be reused between queries.
+pub fn dijkstra<K: Weight, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<K, Handle=Node>>(graph: &G, heap: &mut H, source: Node, target: Node) -> Result<Option<K>, SearchError> {
+    run_dijkstra(graph, heap, source, Some(target), K::infinity(), &mut NoVisitor)
+}
+
+pub fn dijkstra_with_stats<K: Weight, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<K, Handle=Node>>(graph: &G, heap: &mut H, source: Node, target: Node, stats: &mut SearchStats) -> Result<Option<K>, SearchError> {
+    run_dijkstra(graph, heap, source, Some(target), K::infinity(), stats)
+}
+
+pub fn dijkstra_with_visitor<K: Weight, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<K, Handle=Node>, V: SearchVisitor<K>>(graph: &G, heap: &mut H, source: Node, target: Node, visitor: &mut V) -> Result<Option<K>, SearchError> {
+    run_dijkstra(graph, heap, source, Some(target), K::infinity(), visitor)
+}
+
+// Settles every node reachable from source
+pub fn dijkstra_one_to_all<K: Weight, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<K, Handle=Node>, V: SearchVisitor<K>>(graph: &G, heap: &mut H, source: Node, visitor: &mut V) -> Result<(), SearchError> {
+    run_dijkstra(graph, heap, source, None, K::infinity(), visitor).map(|_| ())
+}
+
+// Settles every node reachable from source with a weight of at most max_weight
+pub fn dijkstra_bounded<K: Weight, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<K, Handle=Node>, V: SearchVisitor<K>>(graph: &G, heap: &mut H, source: Node, max_weight: K, visitor: &mut V) -> Result<(), SearchError> {
+    run_dijkstra(graph, heap, source, None, max_weight, visitor).map(|_| ())
+}
+
+fn run_dijkstra<K: Weight, D: WeightedData<K>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<K, Handle=Node>, V: SearchVisitor<K>>(graph: &G, heap: &mut H, source: Node, target: Option<Node>, max_weight: K, visitor: &mut V) -> Result<Option<K>, SearchError> {
+    heap.clear();
+    heap.push(source, K::zero());
 
     loop {
         match heap.pop() {
             None => {
                 break;
             },
-            Some((node, weight)) if node == target => {
-                return Some(weight);
+            Some((_, weight)) if weight > max_weight => {
+                break;
+            },
+            Some((node, weight)) if Some(node) == target => {
+                visitor.settle(node, weight);
+                return Ok(Some(weight));
             },
             Some((node, parent_weight)) => {
+                visitor.settle(node, parent_weight);
                 for adj_edge in graph.edges(node) {
                     let target = graph.target(adj_edge);
                     let edge_weight = graph.data(adj_edge).weight();
-                    let total_weight = parent_weight + edge_weight;
+                    // closed edge or already settled target
+                    if edge_weight == K::infinity() || (!heap.in_heap(target) && heap.was_inserted(target)) {
+                        continue;
+                    }
+                    let total_weight = match parent_weight.checked_add(edge_weight) {
+                        Some(total_weight) => total_weight,
+                        None => return Err(SearchError::Overflow(node, adj_edge))
+                    };
+                    visitor.relax(node, adj_edge, target, total_weight);
                     if heap.in_heap(target) {
-                        heap.decrease(target, total_weight);
+                        if total_weight < heap.key(target) {
+                            heap.decrease(target, total_weight);
+                            visitor.decrease(node, adj_edge, target, total_weight);
+                        }
                     } else {
                         heap.push(target, total_weight);
+                        visitor.push(node, adj_edge, target, total_weight);
                     }
                 }
             }
         }
     }
 
-    None
+    Ok(None)
+}
+
+// Lower bound of the remaining weight to the target of an A* search. It has to be
+// consistent, potential(u) <= weight(u, v) + potential(v) for every edge, otherwise
+// nodes may be settled with a wrong weight. Infinity means the target can not be reached.
+pub trait Potential {
+    fn potential(&mut self, node: Node) -> u32;
+}
+
+pub fn astar<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>, P: Potential>(graph: &G, heap: &mut H, potential: &mut P, source: Node, target: Node) -> Result<Option<u32>, SearchError> {
+    run_astar(graph, heap, potential, source, target, &mut NoVisitor)
+}
+
+pub fn astar_with_stats<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>, P: Potential>(graph: &G, heap: &mut H, potential: &mut P, source: Node, target: Node, stats: &mut SearchStats) -> Result<Option<u32>, SearchError> {
+    run_astar(graph, heap, potential, source, target, stats)
+}
+
+pub fn astar_with_visitor<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>, P: Potential, V: SearchVisitor<u32>>(graph: &G, heap: &mut H, potential: &mut P, source: Node, target: Node, visitor: &mut V) -> Result<Option<u32>, SearchError> {
+    run_astar(graph, heap, potential, source, target, visitor)
+}
+
+// The heap keys are the weight from the source plus the potential,
+// the visitor gets the weight from the source only.
+fn run_astar<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>, P: Potential, V: SearchVisitor<u32>>(graph: &G, heap: &mut H, potential: &mut P, source: Node, target: Node, visitor: &mut V) -> Result<Option<u32>, SearchError> {
+    heap.clear();
+    let source_potential = potential.potential(source);
+    if source_potential == u32::infinity() {
+        return Ok(None);
+    }
+    heap.push(source, source_potential);
+
+    while let Some((node, key)) = heap.pop() {
+        let parent_weight = key - potential.potential(node);
+        visitor.settle(node, parent_weight);
+        if node == target {
+            return Ok(Some(parent_weight));
+        }
+
+        for adj_edge in graph.edges(node) {
+            let adj_target = graph.target(adj_edge);
+            let edge_weight = graph.data(adj_edge).weight();
+            if edge_weight == u32::infinity() || (!heap.in_heap(adj_target) && heap.was_inserted(adj_target)) {
+                continue;
+            }
+            let total_weight = match parent_weight.checked_add(edge_weight) {
+                Some(total_weight) => total_weight,
+                None => return Err(SearchError::Overflow(node, adj_edge))
+            };
+            visitor.relax(node, adj_edge, adj_target, total_weight);
+
+            let target_potential = potential.potential(adj_target);
+            if target_potential == u32::infinity() {
+                continue;
+            }
+            let total_key = match total_weight.checked_add(target_potential) {
+                Some(total_key) => total_key,
+                None => return Err(SearchError::Overflow(node, adj_edge))
+            };
+            if heap.in_heap(adj_target) {
+                if total_key < heap.key(adj_target) {
+                    heap.decrease(adj_target, total_key);
+                    visitor.decrease(node, adj_edge, adj_target, total_weight);
+                }
+            } else {
+                heap.push(adj_target, total_key);
+                visitor.push(node, adj_edge, adj_target, total_weight);
+            }
+        }
+    }
+
+    Ok(None)
 }
 
 #[cfg(test)]
@@ -40,25 +209,155 @@ mod tests {
     use super::*;
     use graph::*;
     use addressable_heap::*;
+    use weight::FloatWeight;
+    use test_util::{TestData, graph};
+
+    // 0 --> 1 ---> 2
+    // |------------^
+    #[test]
+    fn dijkstra_triangle() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (0, 2, TestData {weight: 3})]);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(3);
+        let weight = dijkstra(&graph, &mut heap, 0, 2);
+        assert_eq!(weight, Ok(Some(2)));
+    }
+
+    #[test]
+    fn dijkstra_stats() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (0, 2, TestData {weight: 3})]);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(3);
+        let mut stats = SearchStats::default();
+        let weight = dijkstra_with_stats(&graph, &mut heap, 0, 2, &mut stats);
+        assert_eq!(weight, Ok(Some(2)));
+        assert_eq!(stats, SearchStats {settled: 3, relaxed: 3, decreased: 1});
+
+        // the heap is reused for the next query
+        let weight = dijkstra(&graph, &mut heap, 1, 2);
+        assert_eq!(weight, Ok(Some(1)));
+    }
+
+    struct SettleOrder {
+        order: Vec<(Node, u32)>
+    }
+
+    impl SearchVisitor<u32> for SettleOrder {
+        fn settle(&mut self, node: Node, weight: u32) {
+            self.order.push((node, weight));
+        }
+    }
+
+    #[test]
+    fn dijkstra_visitor() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (0, 2, TestData {weight: 3}), (3, 0, TestData {weight: 1})]);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(4);
+        let mut visitor = SettleOrder {order: vec![]};
+        dijkstra_one_to_all(&graph, &mut heap, 0, &mut visitor).unwrap();
+        assert_eq!(visitor.order, vec![(0, 0), (1, 1), (2, 2)]);
+    }
 
     #[derive(PartialEq,Eq,PartialOrd,Ord)]
-    struct TestData {
-        weight: u32
+    struct MetricData {
+        duration: u32,
+        length: FloatWeight
     }
 
-    impl WeightedData<u32> for TestData {
-        fn weight(&self) -> u32 {
-            self.weight
+    impl WeightedData<FloatWeight> for MetricData {
+        fn weight(&self) -> FloatWeight {
+            self.length
+        }
+    }
+
+    impl WeightedData<(u32, FloatWeight)> for MetricData {
+        fn weight(&self) -> (u32, FloatWeight) {
+            (self.duration, self.length)
         }
     }
 
     // 0 --> 1 ---> 2
     // |------------^
     #[test]
-    fn dijkstra_triangle() {
-        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (0, 2, TestData {weight: 3})]);
+    fn dijkstra_float() {
+        let graph : AdjArrayGraph<MetricData> = AdjArrayGraph::new(vec![(0, 1, MetricData {duration: 1, length: FloatWeight::new(0.5)}), (1, 2, MetricData {duration: 1, length: FloatWeight::new(0.75)}), (0, 2, MetricData {duration: 3, length: FloatWeight::new(1.5)})]);
+        let mut heap : AddressableBinaryHeap<FloatWeight> = AddressableBinaryHeap::new(3);
+        assert_eq!(dijkstra(&graph, &mut heap, 0, 2), Ok(Some(FloatWeight::new(1.25))));
+    }
+
+    // both paths from 0 to 2 take the same time, the shorter one is chosen
+    #[test]
+    fn dijkstra_tie_breaking() {
+        let graph : AdjArrayGraph<MetricData> = AdjArrayGraph::new(vec![(0, 1, MetricData {duration: 1, length: FloatWeight::new(2.0)}), (1, 2, MetricData {duration: 1, length: FloatWeight::new(2.0)}), (0, 2, MetricData {duration: 2, length: FloatWeight::new(3.0)})]);
+        let mut heap : AddressableBinaryHeap<(u32, FloatWeight)> = AddressableBinaryHeap::new(3);
+        assert_eq!(dijkstra(&graph, &mut heap, 0, 2), Ok(Some((2, FloatWeight::new(3.0)))));
+    }
+
+    // 0 <-> 1 <-> 2    3
+    #[test]
+    fn dijkstra_unreachable() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 0, TestData {weight: 1}), (1, 2, TestData {weight: 0}), (2, 1, TestData {weight: 0}), (3, 3, TestData {weight: 1})]);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(4);
+        let weight = dijkstra(&graph, &mut heap, 0, 3);
+        assert_eq!(weight, Ok(None));
+    }
+
+    // 0 --> 1 ---> 2
+    // |------------^
+    #[test]
+    fn dijkstra_overflow() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: u32::max_value() - 2}), (1, 2, TestData {weight: 5}), (0, 2, TestData {weight: 1})]);
         let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(3);
-        let weight = dijkstra(&graph, &mut heap, 0, 2);
-        assert_eq!(weight, Some(2));
+        // the target is settled before the overflowing edge is relaxed
+        assert_eq!(dijkstra(&graph, &mut heap, 0, 2), Ok(Some(1)));
+        // the overflowing edge leads to a settled node and is skipped
+        let mut visitor = NoVisitor;
+        assert_eq!(dijkstra_one_to_all(&graph, &mut heap, 0, &mut visitor), Ok(()));
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: u32::max_value() - 2}), (1, 2, TestData {weight: 5})]);
+        assert_eq!(dijkstra_one_to_all(&graph, &mut heap, 0, &mut visitor), Err(SearchError::Overflow(1, 1)));
+    }
+
+    #[test]
+    fn dijkstra_bounded_settles_up_to_max_weight() {
+        let graph = graph(&[(0, 1, 1), (1, 2, 1), (2, 3, 1)]);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(4);
+        let mut stats = SearchStats::default();
+        assert_eq!(dijkstra_bounded(&graph, &mut heap, 0, 2, &mut stats), Ok(()));
+        assert_eq!(stats.settled, 3);
+    }
+
+    #[test]
+    fn dijkstra_closed_edge() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (0, 2, TestData {weight: u32::max_value()})]);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(3);
+        let mut stats = SearchStats::default();
+        assert_eq!(dijkstra_with_stats(&graph, &mut heap, 0, 2, &mut stats), Ok(Some(2)));
+        assert_eq!(stats, SearchStats {settled: 3, relaxed: 2, decreased: 0});
+        assert_eq!(dijkstra(&graph, &mut heap, 2, 0), Ok(None));
+    }
+
+    // 0 -> 1 -> 2 -> 3
+    // |--> 4 -------^
+    struct LinePotential {
+        target: Node
+    }
+
+    impl Potential for LinePotential {
+        fn potential(&mut self, node: Node) -> u32 {
+            match node {
+                4 => 4,
+                _ if node <= self.target => self.target - node,
+                _ => 0
+            }
+        }
+    }
+
+    #[test]
+    fn astar_line() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (2, 3, TestData {weight: 1}), (0, 4, TestData {weight: 1}), (4, 3, TestData {weight: 5})]);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(5);
+        let mut stats = SearchStats::default();
+        assert_eq!(astar_with_stats(&graph, &mut heap, &mut LinePotential {target: 3}, 0, 3, &mut stats), Ok(Some(3)));
+        // node 4 looks worse than the line and is never settled
+        assert_eq!(stats.settled, 4);
+        assert_eq!(dijkstra(&graph, &mut heap, 0, 3), Ok(Some(3)));
+        assert_eq!(astar(&graph, &mut heap, &mut LinePotential {target: 0}, 3, 0), Ok(None));
     }
 }
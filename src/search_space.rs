@@ -0,0 +1,98 @@
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+use rustc_serialize::json::{Json, ToJson};
+
+use graph::{Node, Edge};
+use search::SearchVisitor;
+use wgs84::WGS84;
+
+use std::collections::BTreeMap;
+
+// Records the settled nodes and the shortest path tree of a search
+// so it can be inspected visually.
+pub struct SearchSpace<K> {
+    // settled nodes with their final weight in settle order
+    pub settled: Vec<(Node, K)>,
+    // last edge on the currently best path to a node
+    pub parents: BTreeMap<Node, (Node, Edge)>
+}
+
+impl<K> SearchSpace<K> {
+    pub fn new() -> SearchSpace<K> {
+        SearchSpace {settled: Vec::new(), parents: BTreeMap::new()}
+    }
+}
+
+impl<K: ToJson> SearchSpace<K> {
+    // Every settled node becomes a point with its weight and settle rank, every
+    // edge of the shortest path tree between settled nodes becomes a line.
+    pub fn to_geojson(&self, coordinates: &[WGS84]) -> GeoJson {
+        let mut features = Vec::new();
+
+        for (rank, &(node, ref weight)) in self.settled.iter().enumerate() {
+            let ref coordinate = coordinates[node as usize];
+            let mut properties = BTreeMap::new();
+            properties.insert(String::from("node"), node.to_json());
+            properties.insert(String::from("weight"), weight.to_json());
+            properties.insert(String::from("rank"), rank.to_json());
+            features.push(to_feature(Value::Point(vec![coordinate.lon, coordinate.lat]), properties));
+
+            if let Some(&(parent, edge)) = self.parents.get(&node) {
+                let ref parent_coordinate = coordinates[parent as usize];
+                let mut properties = BTreeMap::new();
+                properties.insert(String::from("edge"), edge.to_json());
+                let line = vec![vec![parent_coordinate.lon, parent_coordinate.lat], vec![coordinate.lon, coordinate.lat]];
+                features.push(to_feature(Value::LineString(line), properties));
+            }
+        }
+
+        GeoJson::FeatureCollection(FeatureCollection {bbox: None, crs: None, features: features})
+    }
+}
+
+fn to_feature(value: Value, properties: BTreeMap<String, Json>) -> Feature {
+    Feature {bbox: None, crs: None, geometry: Some(Geometry::new(value)), id: None, properties: Some(properties)}
+}
+
+impl<K> SearchVisitor<K> for SearchSpace<K> {
+    fn settle(&mut self, node: Node, weight: K) {
+        self.settled.push((node, weight));
+    }
+
+    fn push(&mut self, source: Node, edge: Edge, target: Node, _: K) {
+        self.parents.insert(target, (source, edge));
+    }
+
+    fn decrease(&mut self, source: Node, edge: Edge, target: Node, _: K) {
+        self.parents.insert(target, (source, edge));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use search::*;
+    use addressable_heap::*;
+    use geojson::GeoJson;
+    use test_util::TestData;
+
+    // 0 --> 1 ---> 2
+    // |------------^
+    #[test]
+    fn triangle_search_space() {
+        let graph : AdjArrayGraph<TestData> = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 1}), (1, 2, TestData {weight: 1}), (0, 2, TestData {weight: 3})]);
+        let coordinates = vec![WGS84 {lon: 0.0, lat: 0.0}, WGS84 {lon: 1.0, lat: 0.0}, WGS84 {lon: 2.0, lat: 0.0}];
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(3);
+        let mut space = SearchSpace::new();
+        assert_eq!(dijkstra_with_visitor(&graph, &mut heap, 0, 2, &mut space), Ok(Some(2)));
+
+        assert_eq!(space.settled, vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(space.parents.get(&2).map(|p| p.0), Some(1));
+
+        let geojson = space.to_geojson(&coordinates).to_string();
+        match geojson.parse::<GeoJson>().unwrap() {
+            GeoJson::FeatureCollection(collection) => assert_eq!(collection.features.len(), 5),
+            _ => panic!("expected a feature collection")
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use graph::{Graph, Node, Edge};
+use graph_builder::RoadNetwork;
+use wgs84::{WGS84, EARTH_RADIUS, haversine};
+
+use std::collections::HashMap;
+
+// closest point of an edge to a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeCandidate {
+    pub source: Node,
+    pub edge: Edge,
+    pub position: WGS84,
+    // meters between the position and the edge
+    pub distance: f64,
+    // meters along the edge from its source to the closest point
+    pub offset: f64
+}
+
+struct Segment {
+    source: Node,
+    edge: Edge,
+    start: WGS84,
+    end: WGS84,
+    // meters along the edge to the start of the segment
+    offset: f64
+}
+
+// Buckets the segments of every edge geometry into a grid of cells of cell_size degrees, so the
+// edges close to a position are found without looking at the whole network.
+pub struct SegmentIndex {
+    cell_size: f64,
+    segments: Vec<Segment>,
+    cells: HashMap<(i32, i32), Vec<u32>>
+}
+
+// closest point on the segment from start to end, in a local projection around the position
+fn closest_point(position: &WGS84, start: &WGS84, end: &WGS84) -> WGS84 {
+    let scale = position.lat.to_radians().cos();
+    let (dx, dy) = ((end.lon - start.lon) * scale, end.lat - start.lat);
+    let (px, py) = ((position.lon - start.lon) * scale, position.lat - start.lat);
+    let squared_length = dx * dx + dy * dy;
+    let t = if squared_length > 0.0 { ((px * dx + py * dy) / squared_length).max(0.0).min(1.0) } else { 0.0 };
+    WGS84 {lon: start.lon + (end.lon - start.lon) * t, lat: start.lat + (end.lat - start.lat) * t}
+}
+
+impl SegmentIndex {
+    pub fn new(network: &RoadNetwork, cell_size: f64) -> SegmentIndex {
+        assert!(cell_size > 0.0, "Cell size must be positive");
+        let mut index = SegmentIndex {cell_size: cell_size, segments: Vec::new(), cells: HashMap::new()};
+        for source in network.graph.nodes() {
+            for edge in network.graph.edges(source) {
+                let line = network.edge_geometry(source, edge);
+                let mut offset = 0.0;
+                for pair in line.windows(2) {
+                    let id = index.segments.len() as u32;
+                    let (min_x, min_y) = index.cell(&WGS84 {lon: pair[0].lon.min(pair[1].lon), lat: pair[0].lat.min(pair[1].lat)});
+                    let (max_x, max_y) = index.cell(&WGS84 {lon: pair[0].lon.max(pair[1].lon), lat: pair[0].lat.max(pair[1].lat)});
+                    for x in min_x..max_x + 1 {
+                        for y in min_y..max_y + 1 {
+                            index.cells.entry((x, y)).or_insert(Vec::new()).push(id);
+                        }
+                    }
+                    index.segments.push(Segment {source: source, edge: edge, start: pair[0], end: pair[1], offset: offset});
+                    offset += haversine(&pair[0], &pair[1]);
+                }
+            }
+        }
+        index
+    }
+
+    fn cell(&self, position: &WGS84) -> (i32, i32) {
+        ((position.lon / self.cell_size).floor() as i32, (position.lat / self.cell_size).floor() as i32)
+    }
+
+    // the closest point of every edge within radius meters, sorted by distance
+    pub fn nearest_edges(&self, position: &WGS84, radius: f64) -> Vec<EdgeCandidate> {
+        let lat_radius = (radius / EARTH_RADIUS).to_degrees();
+        let lon_radius = lat_radius / position.lat.to_radians().cos().max(1e-6);
+        let (min_x, min_y) = self.cell(&WGS84 {lon: position.lon - lon_radius, lat: position.lat - lat_radius});
+        let (max_x, max_y) = self.cell(&WGS84 {lon: position.lon + lon_radius, lat: position.lat + lat_radius});
+
+        let mut closest : HashMap<Edge, EdgeCandidate> = HashMap::new();
+        for x in min_x..max_x + 1 {
+            for y in min_y..max_y + 1 {
+                for &id in self.cells.get(&(x, y)).map_or(&[][..], |ids| &ids[..]) {
+                    let ref segment = self.segments[id as usize];
+                    let point = closest_point(position, &segment.start, &segment.end);
+                    let distance = haversine(position, &point);
+                    let closer = match closest.get(&segment.edge) {
+                        Some(candidate) => distance < candidate.distance,
+                        None => true
+                    };
+                    if distance > radius || !closer {
+                        continue;
+                    }
+                    closest.insert(segment.edge, EdgeCandidate {
+                        source: segment.source,
+                        edge: segment.edge,
+                        position: point,
+                        distance: distance,
+                        offset: segment.offset + haversine(&segment.start, &point)
+                    });
+                }
+            }
+        }
+
+        let mut candidates : Vec<EdgeCandidate> = closest.into_iter().map(|(_, candidate)| candidate).collect();
+        candidates.sort_by(|a, b| a.distance.total_cmp(&b.distance).then(a.edge.cmp(&b.edge)));
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph_builder::*;
+
+    #[test]
+    fn sample_nearest_edges() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let index = SegmentIndex::new(&network, 0.0002);
+        let node = network.id_map[&5];
+        let position = network.coordinates[node as usize];
+
+        // node 5 is the target of the edge from 4 and the source of the edge to 6
+        let candidates = index.nearest_edges(&position, 1.0);
+        assert_eq!(candidates.len(), 2);
+        for candidate in &candidates {
+            assert!(candidate.distance < 1e-6);
+            assert_eq!(candidate.position, position);
+        }
+        let incoming = network.graph.edges(network.id_map[&4]).start;
+        let length = network.graph.data(incoming).length();
+        assert!(candidates.iter().any(|candidate| candidate.edge == incoming && (candidate.offset - length).abs() < 0.1));
+
+        // every edge within the radius, compared to scanning all segments
+        let position = WGS84 {lon: 7.4262, lat: 43.7392};
+        for &radius in &[10.0, 30.0, 60.0, 200.0] {
+            let candidates = index.nearest_edges(&position, radius);
+            let mut expected = Vec::new();
+            for source in network.graph.nodes() {
+                for edge in network.graph.edges(source) {
+                    let line = network.edge_geometry(source, edge);
+                    let distance = line.windows(2).map(|pair| haversine(&position, &closest_point(&position, &pair[0], &pair[1]))).fold(::std::f64::INFINITY, f64::min);
+                    if distance <= radius {
+                        expected.push(edge);
+                    }
+                }
+            }
+            expected.sort();
+            let mut found : Vec<Edge> = candidates.iter().map(|candidate| candidate.edge).collect();
+            found.sort();
+            assert_eq!(found, expected);
+            assert!(candidates.windows(2).all(|pair| pair[0].distance <= pair[1].distance));
+        }
+    }
+
+    #[test]
+    fn compressed_offsets() {
+        let options = LoadOptions {compress: true, ..LoadOptions::default()};
+        let network = load_geojson(&String::from("data/sample.geojson"), &options).unwrap();
+        let index = SegmentIndex::new(&network, 0.0002);
+        let candidates = index.nearest_edges(&network.coordinates[network.id_map[&9] as usize], 5.0);
+        assert_eq!(candidates.len(), 1);
+        let length = network.graph.data(candidates[0].edge).length();
+        assert!((candidates[0].offset - length).abs() < 0.1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_cell_size() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        SegmentIndex::new(&network, 0.0);
+    }
+}
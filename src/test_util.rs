@@ -0,0 +1,92 @@
+// Fixtures shared by the unit tests
+use graph::{Graph, AdjArrayGraph, Node};
+use random::XorShift;
+use search::WeightedData;
+use wgs84::WGS84;
+
+#[derive(Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub struct TestData {
+    pub weight: u32
+}
+
+impl WeightedData<u32> for TestData {
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+}
+
+pub fn graph(edges: &[(Node, Node, u32)]) -> AdjArrayGraph<TestData> {
+    AdjArrayGraph::new(edges.iter().map(|&(source, target, weight)| (source, target, TestData {weight: weight})).collect())
+}
+
+// width x height grid with edges in both directions and one way streets in every third row
+pub fn grid(width: u32, height: u32) -> AdjArrayGraph<TestData> {
+    seeded_grid(width, height, 0).0
+}
+
+// the same grid with weights changed by seed, node coordinates are (x, y)
+pub fn seeded_grid(width: u32, height: u32, seed: u32) -> (AdjArrayGraph<TestData>, Vec<WGS84>) {
+    let mut edges = Vec::new();
+    let mut coordinates = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let node = y * width + x;
+            coordinates.push(WGS84 {lon: x as f64, lat: y as f64});
+            if x + 1 < width {
+                edges.push((node, node + 1, 1 + (x * 7 + y * 3 + seed) % 5));
+                if y % 3 != 0 {
+                    edges.push((node + 1, node, 1 + (x * 5 + y + seed) % 4));
+                }
+            }
+            if y + 1 < height {
+                edges.push((node, node + width, 2 + seed % 2));
+                edges.push((node + width, node, 1 + (x + y + seed) % 3));
+            }
+        }
+    }
+    (graph(&edges), coordinates)
+}
+
+// edge data with two costs for the multi-criteria searches
+#[derive(Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub struct CostData {
+    pub costs: [u32; 2]
+}
+
+pub fn cost_graph(edges: &[(Node, Node, u32, u32)]) -> AdjArrayGraph<CostData> {
+    AdjArrayGraph::new(edges.iter().map(|&(source, target, first, second)| (source, target, CostData {costs: [first, second]})).collect())
+}
+
+// num_edges random edges between num_nodes nodes, both costs are below 10
+pub fn random_cost_graph(random: &mut XorShift, num_nodes: usize, num_edges: usize) -> AdjArrayGraph<CostData> {
+    let mut edges = Vec::new();
+    for _ in 0..num_edges {
+        let source = random.next_below(num_nodes) as Node;
+        let target = random.next_below(num_nodes) as Node;
+        edges.push((source, target, random.next_below(10) as u32, random.next_below(10) as u32));
+    }
+    cost_graph(&edges)
+}
+
+// summed costs of every simple path from source to target, found by depth first search
+pub fn simple_path_costs(graph: &AdjArrayGraph<CostData>, source: Node, target: Node) -> Vec<[u32; 2]> {
+    fn visit(graph: &AdjArrayGraph<CostData>, node: Node, target: Node, costs: [u32; 2], visited: &mut Vec<bool>, result: &mut Vec<[u32; 2]>) {
+        if node == target {
+            result.push(costs);
+            return;
+        }
+        visited[node as usize] = true;
+        for edge in graph.edges(node) {
+            let head = graph.target(edge);
+            if !visited[head as usize] {
+                let edge_costs = graph.data(edge).costs;
+                visit(graph, head, target, [costs[0] + edge_costs[0], costs[1] + edge_costs[1]], visited, result);
+            }
+        }
+        visited[node as usize] = false;
+    }
+
+    let mut result = Vec::new();
+    visit(graph, source, target, [0, 0], &mut vec![false; graph.num_nodes()], &mut result);
+    result
+}
@@ -0,0 +1,346 @@
+use addressable_heap::AddressableHeap;
+use graph::{Graph, Node, Edge};
+use graph_builder::{RoadNetwork, duration};
+use search::{WeightedData, SearchError, SearchStats};
+use weight::Weight;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::BufRead;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FunctionError {
+    Empty,
+    // departure times have to be strictly increasing
+    Unsorted,
+    // a departure time is not smaller than the period
+    OutOfPeriod,
+    // leaving later would arrive earlier
+    NotFifo
+}
+
+// Periodic piecewise linear travel time function, e.g. over one day.
+// Between two points the travel time is interpolated linearly, after the last point
+// it goes back to the first point of the next period. All times are in weight units.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TravelTimeFunction {
+    // (departure time, travel time) sorted by departure time
+    points: Vec<(u32, u32)>,
+    period: u32
+}
+
+impl TravelTimeFunction {
+    // Only FIFO functions are accepted, the travel time never decreases faster than
+    // time passes. Otherwise waiting could pay off and Dijkstra would not be exact.
+    pub fn new(points: Vec<(u32, u32)>, period: u32) -> Result<TravelTimeFunction, FunctionError> {
+        if points.is_empty() {
+            return Err(FunctionError::Empty);
+        }
+        if points.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(FunctionError::Unsorted);
+        }
+        if points.last().unwrap().0 >= period {
+            return Err(FunctionError::OutOfPeriod);
+        }
+        let function = TravelTimeFunction {points: points, period: period};
+        for index in 0..function.points.len() {
+            let (from, to) = function.segment(index);
+            if (to.1 as i64 - from.1 as i64) < -(to.0 - from.0) {
+                return Err(FunctionError::NotFifo);
+            }
+        }
+        Ok(function)
+    }
+
+    pub fn constant(travel_time: u32) -> TravelTimeFunction {
+        TravelTimeFunction {points: vec![(0, travel_time)], period: 1}
+    }
+
+    pub fn period(&self) -> u32 {
+        self.period
+    }
+
+    pub fn points(&self) -> &[(u32, u32)] {
+        &self.points
+    }
+
+    // points at index and index + 1, wrapping around to the first point of the next period
+    fn segment(&self, index: usize) -> ((i64, u32), (i64, u32)) {
+        let (time, travel_time) = self.points[index];
+        let (next_time, next_travel_time) = if index + 1 < self.points.len() {
+            (self.points[index + 1].0 as i64, self.points[index + 1].1)
+        } else {
+            (self.points[0].0 as i64 + self.period as i64, self.points[0].1)
+        };
+        ((time as i64, travel_time), (next_time, next_travel_time))
+    }
+
+    pub fn eval(&self, departure: u32) -> u32 {
+        if self.points.len() == 1 {
+            return self.points[0].1;
+        }
+        let time = (departure % self.period) as i64;
+        // before the first point the last segment of the previous period applies
+        let (index, time) = match self.points.iter().rposition(|&(point, _)| point as i64 <= time) {
+            Some(index) => (index, time),
+            None => (self.points.len() - 1, time + self.period as i64)
+        };
+        let (from, to) = self.segment(index);
+        let fraction = (time - from.0) as f64 / (to.0 - from.0) as f64;
+        (from.1 as f64 + fraction * (to.1 as f64 - from.1 as f64)).round() as u32
+    }
+}
+
+const NO_FUNCTION : u32 = u32::max_value();
+
+// Travel time functions for some edges of a graph,
+// all other edges keep the static weight of their data.
+pub struct TimeDependentWeights {
+    functions: Vec<TravelTimeFunction>,
+    // index into functions for every edge
+    edge_functions: Vec<u32>
+}
+
+impl TimeDependentWeights {
+    pub fn new(num_edges: usize) -> TimeDependentWeights {
+        TimeDependentWeights {functions: Vec::new(), edge_functions: vec![NO_FUNCTION; num_edges]}
+    }
+
+    pub fn set(&mut self, edge: Edge, function: TravelTimeFunction) {
+        let index = self.edge_functions[edge as usize];
+        if index == NO_FUNCTION {
+            self.edge_functions[edge as usize] = self.functions.len() as u32;
+            self.functions.push(function);
+        } else {
+            self.functions[index as usize] = function;
+        }
+    }
+
+    pub fn function(&self, edge: Edge) -> Option<&TravelTimeFunction> {
+        match self.edge_functions[edge as usize] {
+            NO_FUNCTION => None,
+            index => Some(&self.functions[index as usize])
+        }
+    }
+
+    // number of edges with a travel time function
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    pub fn travel_time<D: WeightedData<u32>>(&self, edge: Edge, data: &D, departure: u32) -> u32 {
+        match self.function(edge) {
+            Some(function) => function.eval(departure),
+            None => data.weight()
+        }
+    }
+}
+
+// Earliest arrival at target when leaving source at departure.
+// The heap key of a node is its arrival time, which is exact for FIFO functions.
+pub fn td_dijkstra<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(graph: &G, weights: &TimeDependentWeights, heap: &mut H, source: Node, target: Node, departure: u32) -> Result<Option<u32>, SearchError> {
+    td_dijkstra_with_stats(graph, weights, heap, source, target, departure, &mut SearchStats::default())
+}
+
+pub fn td_dijkstra_with_stats<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>, H: AddressableHeap<u32, Handle=Node>>(graph: &G, weights: &TimeDependentWeights, heap: &mut H, source: Node, target: Node, departure: u32, stats: &mut SearchStats) -> Result<Option<u32>, SearchError> {
+    heap.clear();
+    heap.push(source, departure);
+
+    while let Some((node, arrival)) = heap.pop() {
+        stats.settled += 1;
+        if node == target {
+            return Ok(Some(arrival));
+        }
+        for edge in graph.edges(node) {
+            let edge_target = graph.target(edge);
+            // settled
+            if !heap.in_heap(edge_target) && heap.was_inserted(edge_target) {
+                continue;
+            }
+            let travel_time = weights.travel_time(edge, graph.data(edge), arrival);
+            if travel_time == u32::infinity() {
+                continue;
+            }
+            let total_arrival = match arrival.checked_add(travel_time) {
+                Some(total_arrival) => total_arrival,
+                None => return Err(SearchError::Overflow(node, edge))
+            };
+            stats.relaxed += 1;
+            if heap.in_heap(edge_target) {
+                if total_arrival < heap.key(edge_target) {
+                    heap.decrease(edge_target, total_arrival);
+                    stats.decreased += 1;
+                }
+            } else {
+                heap.push(edge_target, total_arrival);
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+// Reads speed profiles with one point per line:
+//
+//     osm way id,osm id of the first node,osm id of the second node,time of day,speed in km/h
+//
+// The points of one edge form its travel time function, the time of day is in weight units
+// and has to be smaller than period. Empty lines and lines starting with # are ignored.
+// Edges of compressed networks only match if both nodes are the ends of the compressed edge.
+pub fn read_speed_profiles<R: BufRead>(reader: R, network: &RoadNetwork, period: u32) -> io::Result<TimeDependentWeights> {
+    let mut points : BTreeMap<Edge, Vec<(u32, u32)>> = BTreeMap::new();
+    let mut first_lines : BTreeMap<Edge, usize> = BTreeMap::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = try!(line);
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields : Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        if fields.len() != 5 {
+            return Err(invalid_data(line_number, "expected way, from, to, time and speed"));
+        }
+        let ids : Result<Vec<i64>, _> = fields[..3].iter().map(|field| field.parse::<i64>()).collect();
+        let ids = try!(ids.map_err(|_| invalid_data(line_number, "OSM id is not a number")));
+        let time = try!(fields[3].parse::<u32>().map_err(|_| invalid_data(line_number, "time is not a number")));
+        let speed = try!(fields[4].parse::<f64>().map_err(|_| invalid_data(line_number, "speed is not a number")));
+        if speed <= 0.0 || !speed.is_finite() {
+            return Err(invalid_data(line_number, "speed has to be positive"));
+        }
+
+        let edge = match (network.id_map.get(&ids[1]), network.id_map.get(&ids[2])) {
+            (Some(&source), Some(&target)) => network.graph.edges(source).find(|&edge| {
+                network.graph.target(edge) == target && network.way(edge).osm_id == ids[0]
+            }),
+            _ => None
+        };
+        let edge = match edge {
+            Some(edge) => edge,
+            None => return Err(invalid_data(line_number, "no edge for way and nodes"))
+        };
+        let travel_time = duration(network.graph.data(edge).length(), speed);
+        points.entry(edge).or_default().push((time, travel_time));
+        first_lines.entry(edge).or_insert(line_number);
+    }
+
+    let mut weights = TimeDependentWeights::new(network.graph.num_edges());
+    for (edge, mut edge_points) in points {
+        edge_points.sort();
+        match TravelTimeFunction::new(edge_points, period) {
+            Ok(function) => weights.set(edge, function),
+            Err(error) => return Err(invalid_data(first_lines[&edge], &format!("profile is invalid: {:?}", error)))
+        }
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressable_heap::*;
+    use graph::*;
+    use graph_builder::*;
+    use test_util::TestData;
+
+    #[test]
+    fn interpolation() {
+        let function = TravelTimeFunction::new(vec![(0, 100), (100, 200)], 200).unwrap();
+        assert_eq!(function.eval(0), 100);
+        assert_eq!(function.eval(50), 150);
+        assert_eq!(function.eval(100), 200);
+        // back to the first point of the next period
+        assert_eq!(function.eval(150), 150);
+        assert_eq!(function.eval(250), 150);
+        assert_eq!(TravelTimeFunction::constant(7).eval(12345), 7);
+
+        // before the first point
+        let function = TravelTimeFunction::new(vec![(50, 10), (150, 30)], 200).unwrap();
+        assert_eq!(function.eval(0), 20);
+        assert_eq!(function.eval(175), 25);
+    }
+
+    #[test]
+    fn invalid_functions() {
+        assert_eq!(TravelTimeFunction::new(vec![], 10), Err(FunctionError::Empty));
+        assert_eq!(TravelTimeFunction::new(vec![(5, 1), (5, 2)], 10), Err(FunctionError::Unsorted));
+        assert_eq!(TravelTimeFunction::new(vec![(0, 1), (10, 2)], 10), Err(FunctionError::OutOfPeriod));
+        assert_eq!(TravelTimeFunction::new(vec![(0, 100), (10, 50)], 100), Err(FunctionError::NotFifo));
+        // the wrap around is checked as well
+        assert_eq!(TravelTimeFunction::new(vec![(0, 10), (90, 50)], 100), Err(FunctionError::NotFifo));
+        assert!(TravelTimeFunction::new(vec![(0, 100), (50, 50)], 100).is_ok());
+    }
+
+    // 0 -> 1 directly or over 2 where 0 -> 2 is congested between 100 and 200
+    fn rush_hour() -> (AdjArrayGraph<TestData>, TimeDependentWeights) {
+        let graph = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 30}), (0, 2, TestData {weight: 5}), (2, 1, TestData {weight: 5})]);
+        let mut weights = TimeDependentWeights::new(graph.num_edges());
+        let edge = graph.edges(0).find(|&edge| graph.target(edge) == 2).unwrap();
+        weights.set(edge, TravelTimeFunction::new(vec![(0, 5), (90, 5), (100, 50), (200, 50), (250, 5)], 1000).unwrap());
+        (graph, weights)
+    }
+
+    #[test]
+    fn departure_time() {
+        let (graph, weights) = rush_hour();
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        assert_eq!(td_dijkstra(&graph, &weights, &mut heap, 0, 1, 0), Ok(Some(10)));
+        assert_eq!(td_dijkstra(&graph, &weights, &mut heap, 0, 1, 150), Ok(Some(180)));
+        assert_eq!(td_dijkstra(&graph, &weights, &mut heap, 0, 1, 1000), Ok(Some(1010)));
+        assert_eq!(td_dijkstra(&graph, &weights, &mut heap, 1, 0, 0), Ok(None));
+    }
+
+    #[test]
+    fn later_departure_never_arrives_earlier() {
+        let (graph, weights) = rush_hour();
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let arrivals : Vec<u32> = (0..400).map(|departure| td_dijkstra(&graph, &weights, &mut heap, 0, 1, departure).unwrap().unwrap()).collect();
+        assert!(arrivals.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn arrival_overflow() {
+        let graph = AdjArrayGraph::new(vec![(0, 1, TestData {weight: 30})]);
+        let weights = TimeDependentWeights::new(graph.num_edges());
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        assert_eq!(td_dijkstra(&graph, &weights, &mut heap, 0, 1, u32::max_value() - 10), Err(SearchError::Overflow(0, 0)));
+    }
+
+    #[test]
+    fn read_sample_profiles() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let csv = "# way,from,to,time,speed\n2,1,2,0,65\n2,1,2,3600,10\n\n2,2,3,0,30\n";
+        let weights = read_speed_profiles(csv.as_bytes(), &network, 86400).unwrap();
+        assert_eq!(weights.len(), 2);
+
+        let source = network.id_map[&1];
+        let edge = network.graph.edges(source).start;
+        let length = network.graph.data(edge).length();
+        let function = weights.function(edge).unwrap();
+        assert_eq!(function.points(), &[(0, duration(length, 65.0)), (3600, duration(length, 10.0))]);
+        assert!(weights.function(network.graph.edges(network.id_map[&3]).start).is_none());
+    }
+
+    #[test]
+    fn read_invalid_profiles() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let read = |csv: &str| read_speed_profiles(csv.as_bytes(), &network, 86400).err().unwrap().kind();
+        // the way is a one way street
+        assert_eq!(read("2,2,1,0,30"), io::ErrorKind::InvalidData);
+        assert_eq!(read("3,1,2,0,30"), io::ErrorKind::InvalidData);
+        assert_eq!(read("2,1,2,noon,30"), io::ErrorKind::InvalidData);
+        assert_eq!(read("2,1,2,0,0"), io::ErrorKind::InvalidData);
+        assert_eq!(read("2,1,2,0"), io::ErrorKind::InvalidData);
+        assert_eq!(read("2,1,2,90000,30"), io::ErrorKind::InvalidData);
+        // crawling and then speeding up immediately is not FIFO
+        assert_eq!(read("2,1,2,0,1\n2,1,2,1,100"), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,269 @@
+use addressable_heap::{AddressableHeap, AddressableBinaryHeap};
+use ch::{ContractionHierarchy, upward_search, ch_query};
+use graph::Node;
+use search::{SearchError, SearchStats};
+use weight::Weight;
+
+use std::cmp;
+
+// table index of nodes that are not transit nodes
+const NOT_TRANSIT : u32 = u32::max_value();
+
+// (node, weight) pairs, nodes of access lists are indices into the table
+type NodeList = Vec<(u32, u32)>;
+
+// (node, weight) lists of all nodes in one array, the list of node v is at offsets[v]..offsets[v + 1].
+struct NodeLists {
+    offsets: Vec<u32>,
+    entries: Vec<(u32, u32)>
+}
+
+impl NodeLists {
+    fn new() -> NodeLists {
+        NodeLists {offsets: vec![0], entries: Vec::new()}
+    }
+
+    fn push(&mut self, list: &[(u32, u32)]) {
+        self.entries.extend_from_slice(list);
+        self.offsets.push(self.entries.len() as u32);
+    }
+
+    fn list(&self, node: Node) -> &[(u32, u32)] {
+        &self.entries[self.offsets[node as usize] as usize..self.offsets[node as usize + 1] as usize]
+    }
+}
+
+// Transit node routing on top of a contraction hierarchy, see Arz et al., "Transit Node
+// Routing Reconsidered". The most important nodes of the hierarchy are transit nodes with
+// a complete distance table between them. Every long path leaves the source through one
+// of its few access nodes and reaches the target through one of the target's, so only
+// table lookups are needed. Pairs where the path might not cover a transit node are local
+// and use the CH query.
+pub struct TransitNodeRouting {
+    ch: ContractionHierarchy,
+    // index of every node in the table or NOT_TRANSIT
+    transit_index: Vec<u32>,
+    num_transit_nodes: usize,
+    // table[i * num_transit_nodes + j] is the distance from transit node i to j
+    table: Vec<u32>,
+    // (table index, distance) of the transit nodes through which a node leaves or is reached
+    forward_access: NodeLists,
+    backward_access: NodeLists,
+    // non-transit nodes settled by the upward searches that stop at transit nodes, sorted by id.
+    // These are the whole search spaces below the transit nodes, so they take
+    // O(n * search space) memory, see average_local_nodes. Fewer transit nodes mean
+    // larger search spaces.
+    forward_local: NodeLists,
+    backward_local: NodeLists
+}
+
+// minimum of the sums over all nodes contained in both search spaces
+fn meeting_distance(forward: &[(Node, u32)], backward: &[(Node, u32)]) -> u32 {
+    let mut best = u32::infinity();
+    let (mut i, mut j) = (0, 0);
+    while i < forward.len() && j < backward.len() {
+        if forward[i].0 < backward[j].0 {
+            i += 1;
+        } else if forward[i].0 > backward[j].0 {
+            j += 1;
+        } else {
+            best = best.min(forward[i].1.saturating_add(backward[j].1));
+            i += 1;
+            j += 1;
+        }
+    }
+    best
+}
+
+impl TransitNodeRouting {
+    // Uses the num_transit_nodes most important nodes of the hierarchy as transit nodes.
+    pub fn new(ch: ContractionHierarchy, num_transit_nodes: usize) -> Result<TransitNodeRouting, SearchError> {
+        let num_nodes = ch.num_nodes();
+        let num_transit_nodes = cmp::min(num_transit_nodes, num_nodes);
+        let transit_nodes = ch.order().split_off(num_nodes - num_transit_nodes);
+        let mut transit_index = vec![NOT_TRANSIT; num_nodes];
+        for (index, &node) in transit_nodes.iter().enumerate() {
+            transit_index[node as usize] = index as u32;
+        }
+
+        let mut heap = AddressableBinaryHeap::new(num_nodes);
+        let mut stats = SearchStats::default();
+
+        // upward searches from a transit node only reach other transit nodes
+        let mut forward_spaces = Vec::with_capacity(num_transit_nodes);
+        let mut backward_spaces = Vec::with_capacity(num_transit_nodes);
+        for &node in &transit_nodes {
+            forward_spaces.push(try!(upward_search(ch.upward(), &mut heap, node, |_| false, &mut stats)));
+            backward_spaces.push(try!(upward_search(ch.downward(), &mut heap, node, |_| false, &mut stats)));
+        }
+        let mut table = Vec::with_capacity(num_transit_nodes * num_transit_nodes);
+        for forward_space in &forward_spaces {
+            for backward_space in &backward_spaces {
+                table.push(meeting_distance(forward_space, backward_space));
+            }
+        }
+
+        let mut tnr = TransitNodeRouting {
+            ch: ch,
+            transit_index: transit_index,
+            num_transit_nodes: num_transit_nodes,
+            table: table,
+            forward_access: NodeLists::new(),
+            backward_access: NodeLists::new(),
+            forward_local: NodeLists::new(),
+            backward_local: NodeLists::new()
+        };
+        for node in 0..num_nodes as Node {
+            let (access, local) = try!(tnr.access_nodes(&mut heap, node, true));
+            tnr.forward_access.push(&access);
+            tnr.forward_local.push(&local);
+            let (access, local) = try!(tnr.access_nodes(&mut heap, node, false));
+            tnr.backward_access.push(&access);
+            tnr.backward_local.push(&local);
+        }
+        Ok(tnr)
+    }
+
+    // Upward search from node that does not continue at transit nodes. The settled transit
+    // nodes are access nodes, except those that are reached faster through another access node.
+    fn access_nodes(&self, heap: &mut AddressableBinaryHeap<u32>, node: Node, forward: bool) -> Result<(NodeList, NodeList), SearchError> {
+        let graph = if forward { self.ch.upward() } else { self.ch.downward() };
+        let ref transit_index = self.transit_index;
+        let mut stats = SearchStats::default();
+        let space = try!(upward_search(graph, heap, node, |other| transit_index[other as usize] != NOT_TRANSIT, &mut stats));
+
+        let (mut candidates, local) : (NodeList, NodeList) = space.into_iter().partition(|&(other, _)| transit_index[other as usize] != NOT_TRANSIT);
+        candidates.sort_by_key(|&(_, weight)| weight);
+        let mut access : NodeList = Vec::new();
+        for (candidate, weight) in candidates {
+            let index = transit_index[candidate as usize];
+            let dominated = access.iter().any(|&(other, other_weight)| {
+                let between = if forward { self.transit_distance(other, index) } else { self.transit_distance(index, other) };
+                other_weight.saturating_add(between) <= weight
+            });
+            if !dominated {
+                access.push((index, weight));
+            }
+        }
+        Ok((access, local))
+    }
+
+    fn transit_distance(&self, from: u32, to: u32) -> u32 {
+        self.table[from as usize * self.num_transit_nodes + to as usize]
+    }
+
+    pub fn ch(&self) -> &ContractionHierarchy {
+        &self.ch
+    }
+
+    pub fn num_transit_nodes(&self) -> usize {
+        self.num_transit_nodes
+    }
+
+    // average number of forward and backward access nodes per node
+    pub fn average_access_nodes(&self) -> f64 {
+        let num_nodes = self.transit_index.len();
+        if num_nodes == 0 {
+            return 0.0;
+        }
+        (self.forward_access.entries.len() + self.backward_access.entries.len()) as f64 / (2 * num_nodes) as f64
+    }
+
+    // average number of forward and backward local search space entries per node,
+    // the memory of the locality filter
+    pub fn average_local_nodes(&self) -> f64 {
+        let num_nodes = self.transit_index.len();
+        if num_nodes == 0 {
+            return 0.0;
+        }
+        (self.forward_local.entries.len() + self.backward_local.entries.len()) as f64 / (2 * num_nodes) as f64
+    }
+
+    // Locality filter: the pair is local if the searches below the transit nodes meet,
+    // then the most important node of the shortest path may not be a transit node.
+    pub fn is_local(&self, source: Node, target: Node) -> bool {
+        meeting_distance(self.forward_local.list(source), self.backward_local.list(target)) != u32::infinity()
+    }
+
+    pub fn query<H: AddressableHeap<u32, Handle=Node>>(&self, forward_heap: &mut H, backward_heap: &mut H, source: Node, target: Node, stats: &mut SearchStats) -> Result<Option<u32>, SearchError> {
+        if self.is_local(source, target) {
+            return ch_query(&self.ch, forward_heap, backward_heap, source, target, stats);
+        }
+
+        let mut best = u32::infinity();
+        for &(forward_index, forward_weight) in self.forward_access.list(source) {
+            for &(backward_index, backward_weight) in self.backward_access.list(target) {
+                let weight = forward_weight.saturating_add(self.transit_distance(forward_index, backward_index)).saturating_add(backward_weight);
+                best = best.min(weight);
+            }
+        }
+        Ok(if best == u32::infinity() { None } else { Some(best) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::*;
+    use search::*;
+    use test_util::{TestData, graph, grid};
+
+    // checks all pairs and returns the number of local ones
+    fn check_queries(graph: &AdjArrayGraph<TestData>, tnr: &TransitNodeRouting) -> usize {
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let mut backward_heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        let mut stats = SearchStats::default();
+        let mut num_local = 0;
+        for source in graph.nodes() {
+            for target in graph.nodes() {
+                let expected = dijkstra(graph, &mut heap, source, target);
+                assert_eq!(tnr.query(&mut heap, &mut backward_heap, source, target, &mut stats), expected);
+                if tnr.is_local(source, target) {
+                    num_local += 1;
+                }
+            }
+        }
+        num_local
+    }
+
+    #[test]
+    fn grid_queries() {
+        let graph = grid(10, 8);
+        let tnr = TransitNodeRouting::new(ContractionHierarchy::new(&graph).unwrap(), 12).unwrap();
+        assert_eq!(tnr.num_transit_nodes(), 12);
+        let num_local = check_queries(&graph, &tnr);
+        // most pairs are answered from the table
+        assert!(num_local > 0);
+        assert!(num_local < graph.num_nodes() * graph.num_nodes() / 2);
+        // the search spaces below the transit nodes are much smaller than the graph
+        let without_transit = TransitNodeRouting::new(ContractionHierarchy::new(&graph).unwrap(), 0).unwrap();
+        assert!(tnr.average_local_nodes() < without_transit.average_local_nodes() / 2.0);
+    }
+
+    #[test]
+    fn all_transit_nodes() {
+        let graph = grid(5, 4);
+        let tnr = TransitNodeRouting::new(ContractionHierarchy::new(&graph).unwrap(), 100).unwrap();
+        assert_eq!(tnr.num_transit_nodes(), 20);
+        assert_eq!(check_queries(&graph, &tnr), 0);
+        assert_eq!(tnr.average_access_nodes(), 1.0);
+        assert_eq!(tnr.average_local_nodes(), 0.0);
+    }
+
+    #[test]
+    fn no_transit_nodes() {
+        let graph = grid(5, 4);
+        let tnr = TransitNodeRouting::new(ContractionHierarchy::new(&graph).unwrap(), 0).unwrap();
+        assert_eq!(check_queries(&graph, &tnr), 20 * 20);
+    }
+
+    // 0 <-> 1 -> 2    3
+    #[test]
+    fn unreachable() {
+        let graph = graph(&[(0, 1, 1), (1, 0, 2), (1, 2, 3), (3, 3, 1)]);
+        let tnr = TransitNodeRouting::new(ContractionHierarchy::with_order(&graph, &[3, 0, 2, 1]).unwrap(), 1).unwrap();
+        check_queries(&graph, &tnr);
+        assert!(!tnr.is_local(0, 2));
+        assert!(!tnr.is_local(3, 0));
+    }
+}
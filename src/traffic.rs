@@ -0,0 +1,254 @@
+use graph::{Graph, Node, Edge};
+use graph_builder::{RoadNetwork, duration};
+use search::WeightedData;
+use weight::Weight;
+
+use std::io;
+use std::io::BufRead;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LiveWeight {
+    pub weight: u32
+}
+
+impl WeightedData<u32> for LiveWeight {
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+}
+
+// Current weight of every edge, kept apart from the topology so traffic updates don't
+// rebuild the graph. Updates copy the weights only while snapshots of them are alive,
+// so queries running on a snapshot never see a half applied update.
+pub struct LiveWeights {
+    weights: Arc<Vec<LiveWeight>>
+}
+
+// Weights at the time the snapshot was taken, can be shared with other threads
+#[derive(Clone)]
+pub struct WeightSnapshot {
+    weights: Arc<Vec<LiveWeight>>
+}
+
+// The topology of a graph with weights of a weight layer, usable by every search
+pub struct LiveGraph<'a, T, G: 'a> {
+    graph: &'a G,
+    weights: &'a [LiveWeight],
+    data: PhantomData<T>
+}
+
+impl<'a, T, G: Graph<T, N=Node, E=Edge>> Graph<LiveWeight> for LiveGraph<'a, T, G> {
+    type N = Node;
+    type E = Edge;
+
+    fn num_nodes(&self) -> usize {
+        self.graph.num_nodes()
+    }
+
+    fn num_edges(&self) -> usize {
+        self.graph.num_edges()
+    }
+
+    fn nodes(&self) -> Range<Node> {
+        self.graph.nodes()
+    }
+
+    fn edges(&self, id: Node) -> Range<Edge> {
+        self.graph.edges(id)
+    }
+
+    fn target(&self, id: Edge) -> Node {
+        self.graph.target(id)
+    }
+
+    fn data(&self, id: Edge) -> &LiveWeight {
+        &self.weights[id as usize]
+    }
+}
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+impl LiveWeights {
+    // starts with the static weights of the graph
+    pub fn new<D: WeightedData<u32>, G: Graph<D, N=Node, E=Edge>>(graph: &G) -> LiveWeights {
+        let mut weights = Vec::with_capacity(graph.num_edges());
+        for node in graph.nodes() {
+            for edge in graph.edges(node) {
+                weights.push(LiveWeight {weight: graph.data(edge).weight()});
+            }
+        }
+        LiveWeights {weights: Arc::new(weights)}
+    }
+
+    pub fn weight(&self, edge: Edge) -> u32 {
+        self.weights[edge as usize].weight
+    }
+
+    // infinity closes the edge
+    pub fn set(&mut self, edge: Edge, weight: u32) {
+        Arc::make_mut(&mut self.weights)[edge as usize].weight = weight;
+    }
+
+    pub fn snapshot(&self) -> WeightSnapshot {
+        WeightSnapshot {weights: self.weights.clone()}
+    }
+
+    pub fn graph<'a, T, G: Graph<T, N=Node, E=Edge>>(&'a self, graph: &'a G) -> LiveGraph<'a, T, G> {
+        LiveGraph {graph: graph, weights: &self.weights, data: PhantomData}
+    }
+
+    // Applies traffic speeds with one edge per line:
+    //
+    //     osm id of the first node,osm id of the second node,speed in km/h
+    //
+    // All edges between the two nodes get the travel time of their length at that speed,
+    // a speed of 0 closes them. Lines for nodes or edges that are not part of the network
+    // are skipped, empty lines and lines starting with # are ignored. Nothing is applied
+    // if a line is malformed. Returns the number of updated edges.
+    pub fn apply_speeds<R: BufRead>(&mut self, reader: R, network: &RoadNetwork) -> io::Result<usize> {
+        let mut updates = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = try!(line);
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields : Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if fields.len() != 3 {
+                return Err(invalid_data(line_number, "expected from, to and speed"));
+            }
+            let from = try!(fields[0].parse::<i64>().map_err(|_| invalid_data(line_number, "OSM id is not a number")));
+            let to = try!(fields[1].parse::<i64>().map_err(|_| invalid_data(line_number, "OSM id is not a number")));
+            let speed = try!(fields[2].parse::<f64>().map_err(|_| invalid_data(line_number, "speed is not a number")));
+            if speed < 0.0 || !speed.is_finite() {
+                return Err(invalid_data(line_number, "speed must not be negative"));
+            }
+
+            if let (Some(&source), Some(&target)) = (network.id_map.get(&from), network.id_map.get(&to)) {
+                for edge in network.graph.edges(source).filter(|&edge| network.graph.target(edge) == target) {
+                    let weight = if speed == 0.0 {
+                        u32::infinity()
+                    } else {
+                        duration(network.graph.data(edge).length(), speed)
+                    };
+                    updates.push((edge, weight));
+                }
+            }
+        }
+
+        let weights = Arc::make_mut(&mut self.weights);
+        for &(edge, weight) in &updates {
+            weights[edge as usize].weight = weight;
+        }
+        Ok(updates.len())
+    }
+}
+
+impl WeightSnapshot {
+    pub fn weight(&self, edge: Edge) -> u32 {
+        self.weights[edge as usize].weight
+    }
+
+    pub fn graph<'a, T, G: Graph<T, N=Node, E=Edge>>(&'a self, graph: &'a G) -> LiveGraph<'a, T, G> {
+        LiveGraph {graph: graph, weights: &self.weights, data: PhantomData}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressable_heap::*;
+    use graph::*;
+    use graph_builder::*;
+    use search::*;
+    use test_util::TestData;
+
+    use std::thread;
+
+    // 0 -> 1 directly or over 2
+    fn triangle() -> AdjArrayGraph<TestData> {
+        AdjArrayGraph::new(vec![(0, 1, TestData {weight: 30}), (0, 2, TestData {weight: 5}), (2, 1, TestData {weight: 5})])
+    }
+
+    #[test]
+    fn update_weights() {
+        let graph = triangle();
+        let mut weights = LiveWeights::new(&graph);
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        assert_eq!(dijkstra(&weights.graph(&graph), &mut heap, 0, 1), Ok(Some(10)));
+
+        let edge = graph.edges(0).find(|&edge| graph.target(edge) == 2).unwrap();
+        weights.set(edge, 50);
+        assert_eq!(weights.weight(edge), 50);
+        assert_eq!(dijkstra(&weights.graph(&graph), &mut heap, 0, 1), Ok(Some(30)));
+        weights.set(edge, u32::max_value());
+        assert_eq!(dijkstra(&weights.graph(&graph), &mut heap, 0, 1), Ok(Some(30)));
+        // the graph itself is untouched
+        assert_eq!(dijkstra(&graph, &mut heap, 0, 1), Ok(Some(10)));
+    }
+
+    #[test]
+    fn snapshot_is_consistent() {
+        let graph = triangle();
+        let mut weights = LiveWeights::new(&graph);
+        let snapshot = weights.snapshot();
+        for edge in 0..graph.num_edges() as Edge {
+            weights.set(edge, 1);
+        }
+
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+        assert_eq!(dijkstra(&snapshot.graph(&graph), &mut heap, 0, 1), Ok(Some(10)));
+        assert_eq!(dijkstra(&weights.graph(&graph), &mut heap, 0, 1), Ok(Some(1)));
+
+        // queries on another thread keep their snapshot while the weights change
+        let worker_snapshot = weights.snapshot();
+        let worker = thread::spawn(move || {
+            let graph = triangle();
+            let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+            dijkstra(&worker_snapshot.graph(&graph), &mut heap, 0, 1)
+        });
+        weights.set(0, 100);
+        assert_eq!(worker.join().unwrap(), Ok(Some(1)));
+        assert_eq!(snapshot.weight(0), 30);
+    }
+
+    #[test]
+    fn apply_sample_speeds() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let mut weights = LiveWeights::new(&network.graph);
+        let source = network.id_map[&1];
+        let target = network.id_map[&9];
+        let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(network.graph.num_nodes());
+        let free_flow = dijkstra(&network.graph, &mut heap, source, target).unwrap().unwrap();
+
+        // the second line is against the one way direction, the third is not part of the network
+        let updated = weights.apply_speeds("# from,to,speed\n1,2,10\n3,2,10\n100,200,50\n".as_bytes(), &network).unwrap();
+        assert_eq!(updated, 1);
+        let edge = network.graph.edges(source).start;
+        assert_eq!(weights.weight(edge), duration(network.graph.data(edge).length(), 10.0));
+        let congested = dijkstra(&weights.graph(&network.graph), &mut heap, source, target).unwrap().unwrap();
+        assert!(congested > free_flow);
+
+        weights.apply_speeds("5,6,0".as_bytes(), &network).unwrap();
+        assert_eq!(dijkstra(&weights.graph(&network.graph), &mut heap, source, target), Ok(None));
+    }
+
+    #[test]
+    fn malformed_speeds() {
+        let network = load_geojson(&String::from("data/sample.geojson"), &LoadOptions::default()).unwrap();
+        let mut weights = LiveWeights::new(&network.graph);
+        let edge = network.graph.edges(network.id_map[&1]).start;
+        let before = weights.weight(edge);
+        for csv in &["1,2,10\n2,3,fast", "1,2", "1,2,-5", "a,2,10"] {
+            assert_eq!(weights.apply_speeds(csv.as_bytes(), &network).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+        // nothing of the first file was applied
+        assert_eq!(weights.weight(edge), before);
+    }
+}
@@ -0,0 +1,197 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::f64;
+
+// Key of a search. Adding an edge weight to a path weight must never make it smaller,
+// so only non-negative values are valid.
+pub trait Weight: Copy + Ord {
+    // weight of the empty path
+    fn zero() -> Self;
+    // bigger than every valid path weight, marks edges that can not be used
+    fn infinity() -> Self;
+    // sum of both weights, stays at infinity instead of wrapping around
+    fn saturating_add(self, other: Self) -> Self;
+    // sum of both weights or None if it would reach infinity
+    fn checked_add(self, other: Self) -> Option<Self>;
+}
+
+impl Weight for u32 {
+    fn zero() -> u32 {
+        0
+    }
+
+    fn infinity() -> u32 {
+        u32::max_value()
+    }
+
+    fn saturating_add(self, other: u32) -> u32 {
+        u32::saturating_add(self, other)
+    }
+
+    fn checked_add(self, other: u32) -> Option<u32> {
+        u32::checked_add(self, other).and_then(|sum| if sum < u32::max_value() { Some(sum) } else { None })
+    }
+}
+
+impl Weight for u64 {
+    fn zero() -> u64 {
+        0
+    }
+
+    fn infinity() -> u64 {
+        u64::max_value()
+    }
+
+    fn saturating_add(self, other: u64) -> u64 {
+        u64::saturating_add(self, other)
+    }
+
+    fn checked_add(self, other: u64) -> Option<u64> {
+        u64::checked_add(self, other).and_then(|sum| if sum < u64::max_value() { Some(sum) } else { None })
+    }
+}
+
+// Compared lexicographically, e.g. (duration, distance) prefers the shorter of two equally fast paths
+impl<A: Weight, B: Weight> Weight for (A, B) {
+    fn zero() -> (A, B) {
+        (A::zero(), B::zero())
+    }
+
+    fn infinity() -> (A, B) {
+        (A::infinity(), B::infinity())
+    }
+
+    fn saturating_add(self, other: (A, B)) -> (A, B) {
+        (self.0.saturating_add(other.0), self.1.saturating_add(other.1))
+    }
+
+    // overflow of the tie-breaker is an error as well, a wrapped value would break ties incorrectly
+    fn checked_add(self, other: (A, B)) -> Option<(A, B)> {
+        match (self.0.checked_add(other.0), self.1.checked_add(other.1)) {
+            (Some(first), Some(second)) => Some((first, second)),
+            _ => None
+        }
+    }
+}
+
+// f64 with a total order, NaN is rejected on construction.
+// Overflowing sums become infinity, which already saturates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloatWeight(f64);
+
+impl FloatWeight {
+    pub fn new(value: f64) -> FloatWeight {
+        // also rules out NaN, which infinity minus infinity would produce
+        assert!(value >= 0.0, "Weight must not be negative or NaN");
+        FloatWeight(value)
+    }
+
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Eq for FloatWeight {}
+
+impl PartialOrd for FloatWeight {
+    fn partial_cmp(&self, other: &FloatWeight) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloatWeight {
+    fn cmp(&self, other: &FloatWeight) -> Ordering {
+        self.0.partial_cmp(&other.0).unwrap()
+    }
+}
+
+impl fmt::Display for FloatWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Weight for FloatWeight {
+    fn zero() -> FloatWeight {
+        FloatWeight(0.0)
+    }
+
+    fn infinity() -> FloatWeight {
+        FloatWeight(f64::INFINITY)
+    }
+
+    fn saturating_add(self, other: FloatWeight) -> FloatWeight {
+        FloatWeight(self.0 + other.0)
+    }
+
+    fn checked_add(self, other: FloatWeight) -> Option<FloatWeight> {
+        let sum = self.0 + other.0;
+        if sum.is_finite() { Some(FloatWeight(sum)) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_saturates() {
+        assert_eq!(Weight::saturating_add(3u32, 4), 7);
+        assert_eq!(Weight::saturating_add(u32::max_value() - 1, 4), u32::max_value());
+        assert_eq!(Weight::saturating_add(u64::max_value(), 1), u64::max_value());
+    }
+
+    #[test]
+    fn integer_checked() {
+        assert_eq!(Weight::checked_add(3u32, 4), Some(7));
+        // reaching infinity counts as overflow
+        assert_eq!(Weight::checked_add(u32::max_value() - 4, 4), None);
+        assert_eq!(Weight::checked_add(u32::max_value() - 4, 3), Some(u32::max_value() - 1));
+        assert_eq!(Weight::checked_add(u64::max_value(), 1), None);
+    }
+
+    #[test]
+    fn lexicographic_checked() {
+        assert_eq!((1u32, 2u32).checked_add((3, 4)), Some((4, 6)));
+        assert_eq!((1u32, u32::max_value() - 1).checked_add((3, 4)), None);
+        assert_eq!(<(u32, u32)>::infinity(), (u32::max_value(), u32::max_value()));
+    }
+
+    #[test]
+    fn float_checked() {
+        assert_eq!(FloatWeight::new(1.5).checked_add(FloatWeight::new(2.0)), Some(FloatWeight::new(3.5)));
+        assert_eq!(FloatWeight::new(f64::MAX).checked_add(FloatWeight::new(f64::MAX)), None);
+        assert_eq!(FloatWeight::new(1.0).checked_add(FloatWeight::infinity()), None);
+    }
+
+    #[test]
+    fn lexicographic_order() {
+        let a : (u32, u32) = (10, 200);
+        let b : (u32, u32) = (10, 100);
+        assert!(b < a);
+        assert!(a < (11, 0));
+        assert_eq!(a.saturating_add(b), (20, 300));
+        assert_eq!(<(u32, u32)>::zero(), (0, 0));
+    }
+
+    #[test]
+    fn float_order() {
+        let mut weights = [FloatWeight::new(2.5), FloatWeight::new(0.0), FloatWeight::new(f64::INFINITY), FloatWeight::new(1.25)];
+        weights.sort();
+        let values : Vec<f64> = weights.iter().map(|w| w.value()).collect();
+        assert_eq!(values, vec![0.0, 1.25, 2.5, f64::INFINITY]);
+        assert_eq!(FloatWeight::new(f64::MAX).saturating_add(FloatWeight::new(f64::MAX)).value(), f64::INFINITY);
+    }
+
+    #[test]
+    #[should_panic]
+    fn float_nan() {
+        FloatWeight::new(f64::NAN);
+    }
+
+    #[test]
+    #[should_panic]
+    fn float_negative() {
+        FloatWeight::new(-1.0);
+    }
+}
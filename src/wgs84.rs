@@ -1,3 +1,4 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct WGS84 {
     pub lon: f64,
     pub lat: f64
@@ -0,0 +1,155 @@
+extern crate shortestpath;
+
+use shortestpath::addressable_heap::{AddressableHeap, AddressableBinaryHeap};
+use shortestpath::dary_heap::AddressableQuaternaryHeap;
+use shortestpath::pairing_heap::AddressablePairingHeap;
+use shortestpath::radix_heap::AddressableRadixHeap;
+use shortestpath::bucket_queue::BucketQueue;
+use shortestpath::handle_map::{HashHandleMap, PagedHandleMap};
+use shortestpath::random::XorShift;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+const NUM_HANDLES : usize = 64;
+const NUM_STEPS : usize = 20000;
+// keys are pushed in the range [base, base + MAX_KEY_OFFSET)
+const MAX_KEY_OFFSET : usize = 1000;
+
+// Runs random operations on the heap and a trivially correct model side by side.
+// Monotone heaps only get keys that are not smaller than the last popped key.
+fn check_against_model<H: AddressableHeap<u32, Handle=u32>>(mut heap: H, seed: u64, monotone: bool) {
+    let mut random = XorShift::new(seed);
+    let mut model : BTreeMap<u32, u32> = BTreeMap::new();
+    let mut inserted : BTreeSet<u32> = BTreeSet::new();
+    let mut last_min = 0;
+
+    for _ in 0..NUM_STEPS {
+        let handle = random.next_below(NUM_HANDLES) as u32;
+        let base = if monotone { last_min } else { 0 };
+        match random.next_below(24) {
+            0 | 1 | 2 | 3 | 4 | 5 | 6 => {
+                if !inserted.contains(&handle) {
+                    let key = base + random.next_below(MAX_KEY_OFFSET) as u32;
+                    heap.push(handle, key);
+                    model.insert(handle, key);
+                    inserted.insert(handle);
+                }
+            },
+            7 | 8 | 9 | 10 => {
+                if let Some(&key) = model.get(&handle) {
+                    let new_key = base + random.next_below((key - base) as usize + 1) as u32;
+                    heap.decrease(handle, new_key);
+                    model.insert(handle, new_key);
+                }
+            },
+            11 | 12 | 13 | 14 | 15 | 16 | 17 => {
+                let expected = model.values().min().cloned();
+                match heap.pop() {
+                    None => assert_eq!(expected, None),
+                    Some((popped, key)) => {
+                        // ties can be broken arbitrarily
+                        assert_eq!(Some(key), expected);
+                        assert_eq!(model.remove(&popped), Some(key));
+                        last_min = key;
+                    }
+                }
+            },
+            18 => {
+                let expected = model.values().min().cloned();
+                assert_eq!(heap.min().map(|(_, key)| key), expected);
+            },
+            20 | 21 => {
+                if let Some(&key) = model.get(&handle) {
+                    let new_key = key + random.next_below((base as usize + MAX_KEY_OFFSET) - key as usize) as u32;
+                    heap.increase(handle, new_key);
+                    model.insert(handle, new_key);
+                }
+            },
+            22 => {
+                if let Some(key) = model.remove(&handle) {
+                    assert_eq!(heap.remove(handle), key);
+                }
+            },
+            23 => {
+                // pushes handles that were never inserted, otherwise moves the key in both directions
+                if model.contains_key(&handle) || !inserted.contains(&handle) {
+                    let key = base + random.next_below(MAX_KEY_OFFSET) as u32;
+                    heap.update(handle, key);
+                    model.insert(handle, key);
+                    inserted.insert(handle);
+                }
+            },
+            _ => {
+                if random.next_below(50) == 0 {
+                    heap.clear();
+                    model.clear();
+                    inserted.clear();
+                    last_min = 0;
+                }
+            }
+        }
+
+        assert_eq!(heap.len(), model.len());
+        assert_eq!(heap.in_heap(handle), model.contains_key(&handle));
+        assert_eq!(heap.was_inserted(handle), inserted.contains(&handle));
+        if let Some(&key) = model.get(&handle) {
+            assert_eq!(heap.key(handle), key);
+        }
+    }
+}
+
+#[test]
+fn binary_heap_model() {
+    for seed in 1..5 {
+        check_against_model(AddressableBinaryHeap::new(NUM_HANDLES), seed, false);
+    }
+}
+
+#[test]
+fn binary_heap_hash_map_model() {
+    for seed in 1..5 {
+        check_against_model(AddressableBinaryHeap::with_handle_map(HashHandleMap::new()), seed, false);
+    }
+}
+
+#[test]
+fn binary_heap_paged_map_model() {
+    for seed in 1..5 {
+        check_against_model(AddressableBinaryHeap::with_handle_map(PagedHandleMap::new(NUM_HANDLES)), seed, false);
+    }
+}
+
+#[test]
+fn quaternary_heap_model() {
+    for seed in 1..5 {
+        check_against_model(AddressableQuaternaryHeap::new(NUM_HANDLES), seed, false);
+    }
+}
+
+#[test]
+fn quaternary_heap_paged_map_model() {
+    for seed in 1..5 {
+        check_against_model(AddressableQuaternaryHeap::with_handle_map(PagedHandleMap::new(NUM_HANDLES)), seed, false);
+    }
+}
+
+#[test]
+fn pairing_heap_model() {
+    for seed in 1..5 {
+        check_against_model(AddressablePairingHeap::new(NUM_HANDLES), seed, false);
+    }
+}
+
+#[test]
+fn radix_heap_model() {
+    for seed in 1..5 {
+        check_against_model(AddressableRadixHeap::new(NUM_HANDLES), seed, true);
+    }
+}
+
+#[test]
+fn bucket_queue_model() {
+    for seed in 1..5 {
+        check_against_model(BucketQueue::new(NUM_HANDLES, MAX_KEY_OFFSET as u32), seed, true);
+    }
+}
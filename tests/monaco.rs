@@ -4,6 +4,8 @@ use shortestpath::graph_builder::from_geojson;
 use shortestpath::search::dijkstra;
 use shortestpath::graph::Graph;
 use shortestpath::addressable_heap::AddressableBinaryHeap;
+use shortestpath::hub_labels::{HubLabels, degree_order};
+use shortestpath::random::XorShift;
 
 #[test]
 fn load_monaco() {
@@ -13,5 +15,18 @@ fn load_monaco() {
     let start = *id_map.get(&3883559266_i64).unwrap();
     let target = *id_map.get(&25193709_i64).unwrap();
     let weight = dijkstra(&graph, &mut heap, start, target);
-    assert_eq!(weight, Some(815));
+    assert_eq!(weight, Ok(Some(815)));
+}
+
+#[test]
+fn hub_labels_monaco() {
+    let (graph, _) = from_geojson(&String::from("data/monaco.geojson")).unwrap();
+    let labels = HubLabels::new(&graph, &graph.reversed(), &degree_order(&graph)).unwrap();
+    let mut heap : AddressableBinaryHeap<u32> = AddressableBinaryHeap::new(graph.num_nodes());
+    let mut random = XorShift::new(7);
+    for _ in 0..1000 {
+        let source = random.next_below(graph.num_nodes()) as u32;
+        let target = random.next_below(graph.num_nodes()) as u32;
+        assert_eq!(Ok(labels.distance(source, target)), dijkstra(&graph, &mut heap, source, target));
+    }
 }